@@ -0,0 +1,110 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha384};
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+const HLJS_ROOT: &str = "node_modules/@highlightjs/cdn-assets";
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={HLJS_ROOT}");
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").expect("OUT_DIR"));
+    sri(&out_dir.join("sri.rs"));
+    themes(&out_dir.join("themes.rs"));
+    // Only the server serves these files, so don't bother with the work for
+    // the client bundle.
+    if env::var_os("CARGO_FEATURE_SSR").is_none() {
+        return;
+    }
+    precompress(
+        Path::new(HLJS_ROOT),
+        Path::new(HLJS_ROOT),
+        &out_dir.join("precompressed"),
+    );
+}
+
+// The Subresource Integrity hashes of the scripts, as constants to be
+// included by `consts.rs`.
+fn sri(dest: &Path) {
+    let consts = [
+        ("HIGHLIGHT_MIN_JS_SRI", "highlight.min.js"),
+        ("HIGHLIGHT_ES_MIN_JS_SRI", "es/highlight.min.js"),
+    ]
+    .into_iter()
+    .map(|(name, path)| {
+        let bytes = fs::read(Path::new(HLJS_ROOT).join(path))
+            .expect("failed to read script");
+        let hash = STANDARD.encode(Sha384::digest(bytes));
+        format!("pub const {name}: &str = \"sha384-{hash}\";\n")
+    })
+    .collect::<String>();
+    fs::write(dest, consts).expect("failed to write sri.rs");
+}
+
+// The names of the themes under `styles/`, for the theme selector.
+fn themes(dest: &Path) {
+    let mut themes = fs::read_dir(Path::new(HLJS_ROOT).join("styles"))
+        .expect("failed to read styles dir")
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            name.strip_suffix(".min.css").map(str::to_string)
+        })
+        .collect::<Vec<_>>();
+    themes.sort();
+    fs::write(
+        dest,
+        format!("pub const HLJS_THEMES: &[&str] = &{themes:?};\n"),
+    )
+    .expect("failed to write themes.rs");
+}
+
+// Mirror every script and stylesheet under `dir` into `dest` as both the
+// gzip and brotli encoded versions, e.g. `es/highlight.min.js` will produce
+// `es/highlight.min.js.gz` and `es/highlight.min.js.br`.
+fn precompress(root: &Path, dir: &Path, dest: &Path) {
+    for entry in fs::read_dir(dir).expect("failed to read dir") {
+        let path = entry.expect("failed to read dir entry").path();
+        if path.is_dir() {
+            precompress(root, &path, dest);
+            continue;
+        }
+        if !matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("js" | "css")
+        ) {
+            continue;
+        }
+        let relative = path.strip_prefix(root).expect("path under root");
+        let target = dest.join(relative);
+        fs::create_dir_all(target.parent().expect("file has parent"))
+            .expect("failed to create precompressed dir");
+        let bytes = fs::read(&path).expect("failed to read asset");
+
+        let mut gzip = flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::best(),
+        );
+        gzip.write_all(&bytes).expect("failed to gzip asset");
+        fs::write(
+            append_ext(&target, "gz"),
+            gzip.finish().expect("failed to gzip asset"),
+        )
+        .expect("failed to write gzip asset");
+
+        let mut brotli =
+            brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+        brotli.write_all(&bytes).expect("failed to brotli asset");
+        fs::write(append_ext(&target, "br"), brotli.into_inner())
+            .expect("failed to write brotli asset");
+    }
+}
+
+fn append_ext(path: &Path, ext: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(ext);
+    path.into()
+}
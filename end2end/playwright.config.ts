@@ -0,0 +1,47 @@
+import { defineConfig, devices } from "@playwright/test";
+
+// The address the server is started at, as per `site-addr` in Cargo.toml.
+const baseURL = process.env.BASE_URL ?? "http://127.0.0.1:3000";
+
+export default defineConfig({
+  testDir: "./tests",
+  // The examples demonstrate race conditions, so the tests are run one at a
+  // time to keep the timing of each page load from being skewed by others.
+  fullyParallel: false,
+  workers: 1,
+  forbidOnly: !!process.env.CI,
+  retries: 0,
+  timeout: 30_000,
+  reporter: process.env.CI ? "list" : [["list"], ["html", { open: "never" }]],
+  use: {
+    baseURL,
+    trace: "retain-on-failure",
+  },
+  projects: [
+    {
+      name: "chromium",
+      use: {
+        ...devices["Desktop Chrome"],
+        // A locally installed browser may be used instead of the one
+        // downloaded through `npx playwright install chromium`.
+        launchOptions: {
+          executablePath: process.env.CHROMIUM_PATH || undefined,
+        },
+      },
+    },
+  ],
+  // Reuse the server started by `cargo leptos end2end`, otherwise start one.
+  // The `no-sri` feature must be enabled, as the logging of the calls to
+  // `highlightAll()` is injected into highlight.js by the server, as must
+  // `RACE_CONTROL`, for the latency to be set through the cookie.
+  webServer: {
+    command: "cargo leptos serve --features no-sri",
+    cwd: "..",
+    env: { RACE_CONTROL: "1" },
+    url: baseURL,
+    reuseExistingServer: true,
+    timeout: 15 * 60_000,
+    stdout: "ignore",
+    stderr: "pipe",
+  },
+});
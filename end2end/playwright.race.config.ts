@@ -0,0 +1,12 @@
+import { defineConfig } from "@playwright/test";
+import base from "./playwright.config";
+
+// The race driver (see `race/race.spec.ts`), which is run on its own as it
+// explores rather than asserts, and takes a while:
+//
+//     npx playwright test -c playwright.race.config.ts
+export default defineConfig({
+  ...base,
+  testDir: "./race",
+  reporter: "list",
+});
@@ -0,0 +1,257 @@
+// The race driver: loads each of the walkthrough examples with the events
+// they race against each other held back by the server (see `src/race.rs`),
+// releasing them one at a time in every order, and records whether the
+// page ends up panicking during hydration and how much of its code ends up
+// highlighted.  The results are written as a matrix per example (orderings
+// by renderings) to `race-results/matrix.md` (and `.json`).
+//
+// The examples and renderings may be narrowed through the comma separated
+// `RACE_EXAMPLES` (slugs) and `RACE_RENDERINGS` (SSR modes and/or `csr`).
+import { expect, test, type Page } from "@playwright/test";
+import * as fs from "node:fs";
+import * as path from "node:path";
+import { status, type Status } from "../tests/status";
+
+type Gate = "hljs" | "fetch_code" | "hydrate";
+const GATES: Gate[] = ["hljs", "fetch_code", "hydrate"];
+
+// The SSR modes the examples are served under (`examples::SSR_MODES`), and
+// client-side navigation from the hydrated home page.
+const ONLY_RENDERINGS = process.env.RACE_RENDERINGS?.split(",");
+const RENDERINGS = [
+  "out-of-order",
+  "partially-blocked",
+  "in-order",
+  "async",
+  "csr",
+].filter(
+  (rendering) => !ONLY_RENDERINGS || ONLY_RENDERINGS.includes(rendering),
+);
+
+const RACE_COOKIE = "race_run";
+const LATENCY_COOKIE = "hljs_latency";
+// How long a released event is given to happen before the ordering is taken
+// to be impossible (i.e. the event depends on one still held back).
+const STEP_MS = 3_000;
+const SETTLE_MS = 500;
+
+interface Run {
+  order: Gate[];
+  rendering: string;
+  // the first released event that couldn't happen in the order given
+  blocked?: Gate;
+  // the events that never happened at all (e.g. `hljs` for the examples
+  // not loading the standard highlight.js)
+  absent: Gate[];
+  status?: Status;
+}
+
+function permutations<T>(items: T[]): T[][] {
+  if (items.length <= 1) {
+    return [items];
+  }
+  return items.flatMap((item, n) =>
+    permutations([...items.slice(0, n), ...items.slice(n + 1)]).map(
+      (rest) => [item, ...rest],
+    ),
+  );
+}
+
+async function until(check: () => Promise<boolean> | boolean, ms: number) {
+  const deadline = Date.now() + ms;
+  while (Date.now() < deadline) {
+    if (await check()) {
+      return true;
+    }
+    await new Promise((resolve) => setTimeout(resolve, 50));
+  }
+  return false;
+}
+
+// Track the events as they happen on the page.
+function track(page: Page) {
+  const seen = { hljsRequested: false, hljsLoaded: false };
+  page.on("request", (req) => {
+    if (new URL(req.url()).pathname === "/highlight.min.js") {
+      seen.hljsRequested = true;
+    }
+  });
+  page.on("requestfinished", (req) => {
+    if (new URL(req.url()).pathname === "/highlight.min.js") {
+      seen.hljsLoaded = true;
+    }
+  });
+  const happened = async (gate: Gate) => {
+    switch (gate) {
+      case "hljs":
+        return seen.hljsLoaded;
+      case "fetch_code":
+        return page
+          .evaluate(() => {
+            const demo = document.querySelector("#code-demo");
+            return (
+              demo !== null &&
+              !demo.textContent?.includes("Loading code example")
+            );
+          })
+          .catch(() => false);
+      case "hydrate":
+        return page
+          .evaluate(
+            () =>
+              (window as any)._leptos_hydrated === true ||
+              document.querySelector("#notice.panicked") !== null,
+          )
+          .catch(() => false);
+    }
+  };
+  return { seen, happened };
+}
+
+async function race(
+  page: Page,
+  baseURL: string,
+  slug: string,
+  rendering: string,
+  order: Gate[],
+): Promise<Run> {
+  const run: Run = { order, rendering, absent: [] };
+  const id = `${slug}-${rendering}-${order.join("-")}-${Date.now()}`;
+  const context = page.context();
+  await context.addCookies([
+    { name: RACE_COOKIE, value: id, url: baseURL },
+    { name: LATENCY_COOKIE, value: "0", url: baseURL },
+  ]);
+  const release = async (gate: Gate) => {
+    const res = await context.request.post(`/race/${id}/${gate}`);
+    expect(res.status(), "race control enabled (RACE_CONTROL=1)").toBe(204);
+  };
+  const { seen, happened } = track(page);
+
+  let pending = [...order];
+  if (rendering === "csr") {
+    // the home page is hydrated before navigating to the example, so only
+    // the orderings with the hydration first are possible
+    if (order[0] !== "hydrate") {
+      return { ...run, blocked: order[0] };
+    }
+    await release("hydrate");
+    await page.goto("/");
+    await page.waitForFunction(() => (window as any)._leptos_hydrated);
+    await page.locator(`nav a[href="/${slug}"]`).click();
+    pending = pending.slice(1);
+  } else {
+    // not awaited, as the response itself may be held back
+    page.goto(`/ssr/${rendering}/${slug}`).catch(() => {});
+  }
+
+  for (const gate of pending) {
+    await release(gate);
+    if (!(await until(() => happened(gate), STEP_MS))) {
+      if (gate === "hljs" && !seen.hljsRequested) {
+        run.absent.push(gate);
+      } else {
+        run.blocked ??= gate;
+      }
+    }
+  }
+  // every gate is released by now, so let the page finish whatever is left
+  await until(() => happened("hydrate"), STEP_MS);
+  await page.waitForLoadState("networkidle").catch(() => {});
+  await page.waitForTimeout(SETTLE_MS);
+  if (!seen.hljsRequested && !run.absent.includes("hljs")) {
+    run.absent.push("hljs");
+  }
+  run.status = await status(page);
+  await context.request.delete(`/race/${id}`);
+  return run;
+}
+
+function cell(run: Run): string {
+  if (!run.status) {
+    return "n/a";
+  }
+  const { panicked, highlighted, blocks } = run.status;
+  const outcome = `${panicked ? "**panic**" : "ok"}, ${highlighted}/${blocks} highlighted`;
+  return run.blocked ? `(${run.blocked} blocked) ${outcome}` : outcome;
+}
+
+function matrix(slug: string, runs: Run[]): string {
+  const orders = permutations(GATES).map((order) => order.join(" → "));
+  const renderings = [...new Set(runs.map((run) => run.rendering))];
+  const absent = [...new Set(runs.flatMap((run) => run.absent))];
+  const lines = [
+    `## /${slug}`,
+    "",
+    ...(absent.length ? [`Never happened: ${absent.join(", ")}`, ""] : []),
+    `| order | ${renderings.join(" | ")} |`,
+    `|---|${renderings.map(() => "---").join("|")}|`,
+    ...orders.map((order) => {
+      const cells = renderings.map((rendering) => {
+        const run = runs.find(
+          (run) =>
+            run.rendering === rendering && run.order.join(" → ") === order,
+        );
+        return run ? cell(run) : "";
+      });
+      return `| ${order} | ${cells.join(" | ")} |`;
+    }),
+    "",
+  ];
+  return lines.join("\n");
+}
+
+test("race every ordering of the events", async ({ browser, baseURL }) => {
+  // the orderings are explored for every example of the walkthrough
+  const page = await browser.newPage({ baseURL });
+  await page.goto("/");
+  let slugs = await page
+    .locator("nav a.example, nav a.subexample")
+    .evaluateAll((links) =>
+      links.map((link) =>
+        new URL((link as HTMLAnchorElement).href).pathname.slice(1),
+      ),
+    );
+  await page.close();
+  const only = process.env.RACE_EXAMPLES?.split(",");
+  if (only) {
+    slugs = slugs.filter((slug) => only.includes(slug));
+  }
+  test.setTimeout(
+    slugs.length * RENDERINGS.length * 6 * (GATES.length + 2) * STEP_MS,
+  );
+
+  const results = new Map<string, Run[]>();
+  for (const slug of slugs) {
+    const runs: Run[] = [];
+    for (const rendering of RENDERINGS) {
+      for (const order of permutations(GATES)) {
+        // a fresh context each time, so nothing is cached between runs
+        const context = await browser.newContext({ baseURL });
+        const page = await context.newPage();
+        runs.push(await race(page, baseURL!, slug, rendering, order));
+        await context.close();
+      }
+    }
+    results.set(slug, runs);
+  }
+
+  const dir = path.join(__dirname, "..", "race-results");
+  fs.mkdirSync(dir, { recursive: true });
+  const report = [
+    "# Hydration race matrix",
+    "",
+    "Each cell is the outcome of releasing the events in the order given:",
+    "whether hydration panicked, and how many of the code blocks of the",
+    "demo ended up highlighted.  Where an event couldn't happen in its turn",
+    "as it depends on an event still held back, it is noted as blocked.",
+    "",
+    ...[...results].map(([slug, runs]) => matrix(slug, runs)),
+  ].join("\n");
+  fs.writeFileSync(path.join(dir, "matrix.md"), report);
+  fs.writeFileSync(
+    path.join(dir, "matrix.json"),
+    JSON.stringify(Object.fromEntries(results), null, 2),
+  );
+  console.log(report);
+});
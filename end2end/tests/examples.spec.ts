@@ -0,0 +1,109 @@
+import { expect, test, type Page } from "@playwright/test";
+import { EXPECTED, LATENCIES, type Outcome } from "./expected";
+import { status, type Status } from "./status";
+
+// Fixes the latency of the standard highlight.js for the requests made
+// (see `latency::LATENCY_COOKIE`).
+const LATENCY_COOKIE = "hljs_latency";
+// Logged by the server-instrumented highlight.js on every call.
+const HIGHLIGHT_ALL_LOG = /highlight\.js: highlightAll\(\) called$/;
+// The time given after the page is done loading for anything delayed (e.g.
+// a `requestAnimationFrame` callback) to happen, on top of the latency.
+const SETTLE_MS = 500;
+
+// Count the calls to `highlightAll()` made from here on.
+function countHighlightAll(page: Page): () => number {
+  let calls = 0;
+  page.on("console", (msg) => {
+    if (HIGHLIGHT_ALL_LOG.test(msg.text())) {
+      calls += 1;
+    }
+  });
+  return () => calls;
+}
+
+async function settle(page: Page, latency: number) {
+  await page.waitForFunction(
+    () =>
+      (window as any)._leptos_hydrated === true ||
+      document.querySelector("#notice.panicked") !== null,
+  );
+  await page.waitForLoadState("networkidle");
+  await page.waitForTimeout(latency + SETTLE_MS);
+}
+
+function check(actual: Status, calls: number, expected: Outcome) {
+  // a panic during hydration leaves it incomplete
+  expect(actual.hydrated, "hydrated unless panicked").toBe(!actual.panicked);
+  if (expected.panicked !== undefined) {
+    expect(actual.panicked, "panicked").toBe(expected.panicked);
+  }
+  switch (expected.highlighted) {
+    case "all":
+      expect(actual.blocks, "code blocks").toBeGreaterThan(0);
+      expect(actual.highlighted, "highlighted blocks").toBe(actual.blocks);
+      break;
+    case "some":
+      expect(actual.highlighted, "highlighted blocks").toBeGreaterThan(0);
+      expect(actual.highlighted, "highlighted blocks").toBeLessThan(
+        actual.blocks,
+      );
+      break;
+    case "none":
+      expect(actual.highlighted, "highlighted blocks").toBe(0);
+      break;
+  }
+  if (typeof expected.calls === "number") {
+    expect(calls, "highlightAll() calls").toBe(expected.calls);
+  } else if (expected.calls !== undefined) {
+    const [min, max] = expected.calls;
+    expect(calls, "highlightAll() calls").toBeGreaterThanOrEqual(min);
+    expect(calls, "highlightAll() calls").toBeLessThanOrEqual(max);
+  }
+}
+
+test("every route in the navigation has an expected outcome", async ({
+  page,
+}) => {
+  await page.goto("/");
+  const paths = await page
+    .locator("nav a")
+    .evaluateAll((links) =>
+      links.map((link) => new URL((link as HTMLAnchorElement).href).pathname),
+    );
+  // the restart link also leads home
+  const slugs = [...new Set(paths.map((path) => path.slice(1)))];
+  expect(slugs.sort()).toEqual(Object.keys(EXPECTED).sort());
+});
+
+for (const [slug, expected] of Object.entries(EXPECTED)) {
+  for (const latency of LATENCIES) {
+    test.describe(`/${slug} with ${latency} ms latency`, () => {
+      test.beforeEach(async ({ context, baseURL }) => {
+        await context.addCookies([
+          { name: LATENCY_COOKIE, value: `${latency}`, url: baseURL! },
+        ]);
+      });
+
+      test("loaded through SSR", async ({ page }) => {
+        const calls = countHighlightAll(page);
+        await page.goto(`/${slug}`);
+        await settle(page, latency);
+        check(await status(page), calls(), expected.ssr);
+      });
+
+      const csr = expected.csr;
+      if (csr) {
+        test("navigated to on the client", async ({ page }) => {
+          const calls = countHighlightAll(page);
+          await page.goto("/");
+          await settle(page, 0);
+          await page.locator(`nav a[href="/${slug}"]`).click();
+          await page.waitForURL(`/${slug}`);
+          await settle(page, latency);
+          check(await status(page), calls(), csr);
+        });
+      }
+    });
+  }
+}
@@ -0,0 +1,91 @@
+// The expected outcome of loading each of the routes, as documented on the
+// example pages.  The outcomes are given for the page being freshly loaded
+// (and so rendered through SSR then hydrated), and for the page being
+// navigated to on the client from the home page after it is hydrated.
+//
+// Where a page documents a race condition (e.g. hydration panicking only
+// some of the time depending on when highlight.js is loaded), that part of
+// the outcome is left undefined and so is not asserted.
+
+/// Whether all, some or none of the code blocks of the demo are highlighted.
+export type Highlighted = "all" | "some" | "none";
+
+export interface Outcome {
+  highlighted?: Highlighted;
+  panicked?: boolean;
+  /// The number of calls to `highlightAll()`, or the inclusive range of it.
+  calls?: number | [number, number];
+}
+
+export interface Expected {
+  ssr: Outcome;
+  csr?: Outcome;
+}
+
+/// The latency values (in ms) for the loading of the standard highlight.js,
+/// as cycled through by the server in `main.rs`.
+export const LATENCIES = [0, 4, 40, 400];
+
+// The pages that don't call on highlight.js to do anything to the DOM.
+const QUIET: Outcome = { panicked: false, calls: 0 };
+
+export const EXPECTED: Record<string, Expected> = {
+  "": { ssr: QUIET },
+  // highlight.js is loaded synchronously and highlights before hydration,
+  // but when the component is rendered on the client the inline script
+  // runs before highlight.js is loaded.
+  naive: {
+    ssr: { highlighted: "all", panicked: true, calls: 1 },
+    csr: { highlighted: "none", panicked: false, calls: 0 },
+  },
+  "naive-alt": {
+    ssr: { highlighted: "all", calls: 1 },
+    csr: { panicked: false, calls: [0, 1] },
+  },
+  "naive-hook": {
+    ssr: { highlighted: "all", calls: 1 },
+    csr: { panicked: false, calls: [0, 1] },
+  },
+  "naive-fallback": {
+    ssr: { highlighted: "all", calls: 1 },
+    csr: { highlighted: "all", panicked: false, calls: 1 },
+  },
+  "signal-effect-script": {
+    ssr: { highlighted: "all", panicked: false, calls: 1 },
+    csr: { highlighted: "all", panicked: false, calls: 1 },
+  },
+  "custom-event": {
+    ssr: { highlighted: "all", panicked: false, calls: 1 },
+    csr: { highlighted: "all", panicked: false, calls: 1 },
+  },
+  // highlightAll() is called before the suspended view is mounted, which
+  // is during hydration for SSR.
+  "wasm-bindgen-naive": {
+    ssr: { highlighted: "all", panicked: true, calls: 1 },
+    csr: { highlighted: "some", panicked: false, calls: 1 },
+  },
+  // Both the hydration and the `hljs_hook` listeners may fire.
+  "wasm-bindgen-event": {
+    ssr: { highlighted: "all", panicked: false, calls: [1, 2] },
+    csr: { highlighted: "all", panicked: false, calls: [1, 2] },
+  },
+  "wasm-bindgen-effect": {
+    ssr: { highlighted: "all", panicked: false, calls: [1, 2] },
+    csr: { highlighted: "all", panicked: false, calls: [1, 2] },
+  },
+  // The markup from `hljs::highlight` isn't applied by hydration.
+  "wasm-bindgen-direct": {
+    ssr: { highlighted: "none", panicked: false, calls: 0 },
+    csr: { highlighted: "all", panicked: false, calls: 0 },
+  },
+  "wasm-bindgen-direct-fixed": {
+    ssr: { highlighted: "all", panicked: false, calls: 0 },
+    csr: { highlighted: "all", panicked: false, calls: 0 },
+  },
+  streaming: { ssr: QUIET, csr: QUIET },
+  editor: { ssr: QUIET, csr: QUIET },
+  markdown: { ssr: QUIET, csr: QUIET },
+  // The framed pages log to the same console, so the calls aren't counted.
+  compare: { ssr: { panicked: false }, csr: { panicked: false } },
+  "remote-highlight": { ssr: QUIET, csr: QUIET },
+};
@@ -0,0 +1,29 @@
+import type { Page } from "@playwright/test";
+
+// The state of an example page, as far as the tests are concerned.
+export interface Status {
+  hydrated: boolean;
+  panicked: boolean;
+  highlighted: number;
+  blocks: number;
+}
+
+export async function status(page: Page): Promise<Status> {
+  return page.evaluate(() => {
+    // the code blocks of the demo, where the page has one
+    const demo = document.querySelectorAll("#code-demo pre code");
+    const blocks = [
+      ...(demo.length ? demo : document.querySelectorAll("pre code")),
+    ];
+    return {
+      hydrated: (window as any)._leptos_hydrated === true,
+      panicked: document.querySelector("#notice.panicked") !== null,
+      highlighted: blocks.filter(
+        (code) =>
+          code.classList.contains("hljs") ||
+          code.querySelector('[class^="hljs-"]') !== null,
+      ).length,
+      blocks: blocks.length,
+    };
+  });
+}
@@ -1,8 +1,443 @@
-use leptos::{prelude::ServerFnError, server};
+use futures::{Stream, StreamExt};
+use leptos::{
+    prelude::ServerFnError,
+    server,
+    server_fn::codec::{Json, StreamingText, TextStream},
+};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use thiserror::Error;
 
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Code {
+    /// The shown lines of the code, i.e. the excerpt when one was selected.
+    pub source: String,
+    // The language name as understood by highlight.js
+    pub lang: String,
+    /// The line number of the first line of `source` in the original code.
+    pub start_line: usize,
+    /// The shown and hidden lines in the order they appear in the original.
+    pub segments: Vec<Segment>,
+}
+
+impl Code {
+    /// The code in its entirety, with every line shown.
+    pub fn new(source: impl Into<String>, lang: impl Into<String>) -> Self {
+        let source = source.into();
+        Self {
+            segments: vec![Segment::Shown(source.clone())],
+            source,
+            lang: lang.into(),
+            start_line: 1,
+        }
+    }
+}
+
+/// The path under which the shown lines of code are served as plain text by
+/// their id (see `raw_code_url`), for the copy buttons to fall back on.
+pub const RAW_CODE_PATH: &str = "/code";
+
+/// The URL of the shown lines of the code fetched by `id` (as per
+/// `fetch_code`), with its fragment percent-encoded into the path.
+pub fn raw_code_url(id: &str) -> String {
+    let mut url = format!("{RAW_CODE_PATH}/");
+    for byte in id.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~/".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    url
+}
+
+/// A run of consecutive lines of code, which are either shown as part of
+/// the excerpt or hidden (i.e. rendered collapsed).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Segment {
+    Shown(String),
+    Hidden(String),
+}
+
+/// The reasons why code could not be provided by `fetch_code`, which are
+/// passed to the client as `ServerFnError::WrappedServerError`.
+#[derive(Clone, Debug, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum CodeError {
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("forbidden: {0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+// The inverse of the `Display` provided by `Error`.
+impl FromStr for CodeError {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(if let Some(msg) = s.strip_prefix("not found: ") {
+            CodeError::NotFound(msg.to_string())
+        } else if let Some(msg) = s.strip_prefix("forbidden: ") {
+            CodeError::Forbidden(msg.to_string())
+        } else {
+            CodeError::Other(s.to_string())
+        })
+    }
+}
+
+/// Fetch the code by its `id`, which may have a fragment selecting the lines
+/// to be shown, either as a line range (e.g. `ch05_02a#L11-L13`) or as a
+/// region delimited by `ANCHOR: name`/`ANCHOR_END: name` (e.g. `file.rs#name`).
 #[server]
-pub async fn fetch_code() -> Result<String, ServerFnError> {
-    // emulate loading of code from a database/version control/etc
+pub async fn fetch_code(
+    id: String,
+    lang: Option<String>,
+) -> Result<Code, ServerFnError<CodeError>> {
+    let span = tracing::info_span!("fetch_code", id);
+    crate::server_timing::timed("fetch_code", span, async move {
+        emulate_latency().await;
+        load_code(code_source(), id, lang).await
+    })
+    .await
+}
+
+/// The most `ids` accepted by a single `fetch_codes`.
+pub const FETCH_CODES_MAX_IDS: usize = 64;
+
+/// Fetch the code for every one of the `ids` (as per `fetch_code`) in one
+/// round trip, with the result for each in the same order as the `ids`.
+#[server(input = Json)]
+pub async fn fetch_codes(
+    ids: Vec<String>,
+    lang: Option<String>,
+) -> Result<Vec<Result<Code, CodeError>>, ServerFnError<CodeError>> {
+    if ids.len() > FETCH_CODES_MAX_IDS {
+        return Err(ServerFnError::Args(format!(
+            "{} ids requested, exceeding the limit of {FETCH_CODES_MAX_IDS}",
+            ids.len()
+        )));
+    }
+    let span = tracing::info_span!("fetch_codes", count = ids.len());
+    let source = code_source();
+    let results = crate::server_timing::timed("fetch_code", span, async {
+        // the latency is only incurred once for the whole batch
+        emulate_latency().await;
+        futures::future::join_all(
+            ids.into_iter()
+                .map(|id| load_code(source.clone(), id, lang.clone())),
+        )
+        .await
+    })
+    .await;
+    Ok(results
+        .into_iter()
+        .map(|result| {
+            result.map_err(|err| match err {
+                ServerFnError::WrappedServerError(err) => err,
+                err => CodeError::Other(err.to_string()),
+            })
+        })
+        .collect())
+}
+
+// Emulate the latency of loading code from a database/version control/etc,
+// which the race driver may also hold back (see `race`).
+#[cfg(feature = "ssr")]
+async fn emulate_latency() {
     tokio::time::sleep(std::time::Duration::from_millis(50)).await;
-    Ok(crate::consts::CH05_02A.to_string())
+    if let Some(parts) =
+        leptos::prelude::use_context::<axum::http::request::Parts>()
+    {
+        crate::race::wait(&parts.headers, crate::race::Gate::FetchCode).await;
+    }
+}
+
+// The source is provided by `server::router` to the pages and server fns
+// alike, so there is no sensible fallback should it be missing: serving
+// something other than what was configured would only hide the mistake.
+#[cfg(feature = "ssr")]
+fn code_source() -> crate::source::SharedCodeSource {
+    leptos::prelude::expect_context::<crate::source::SharedCodeSource>()
+}
+
+// Load the code from the source, extracting the selection in the fragment of
+// the `id` should one be present.
+#[cfg(feature = "ssr")]
+pub(crate) async fn load_code(
+    source: crate::source::SharedCodeSource,
+    id: String,
+    lang: Option<String>,
+) -> Result<Code, ServerFnError<CodeError>> {
+    use crate::excerpt::{extract, split_id};
+
+    let (base, selection) = split_id(&id).map_err(|err| {
+        ServerFnError::WrappedServerError(CodeError::Other(err))
+    })?;
+    let base = base.to_string();
+    let mut code = tokio::task::spawn_blocking(move || {
+        source.fetch(&base, lang.as_deref())
+    })
+    .await
+    .map_err(|err| ServerFnError::ServerError(err.to_string()))?
+    .map_err(|err| ServerFnError::WrappedServerError(err.into()))?;
+    // Only Rust follows the convention of hiding lines prefixed with `# `.
+    let excerpt =
+        extract(&code.source, selection.as_ref(), code.lang == "rust")
+            .ok_or_else(|| {
+                ServerFnError::WrappedServerError(CodeError::NotFound(format!(
+                    "selection `{id}` not found"
+                )))
+            })?;
+    code.source = excerpt
+        .segments
+        .iter()
+        .filter_map(|segment| match segment {
+            Segment::Shown(s) => Some(s.as_str()),
+            Segment::Hidden(_) => None,
+        })
+        .collect();
+    code.start_line = excerpt.start_line;
+    code.segments = excerpt.segments;
+    Ok(code)
+}
+
+/// Code highlighted by the server, along with the CSS classes used in the
+/// markup (e.g. `hljs-keyword`).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighlightedCode {
+    pub html: String,
+    pub classes: Vec<String>,
+}
+
+/// The largest code (in bytes) accepted by `highlight_code`.
+pub const HIGHLIGHT_MAX_SIZE: usize = 1024 * 1024;
+
+/// Highlight the code on the server, producing the same markup as
+/// highlight.js would such that clients need not run it at all.  The markup
+/// only consists of escaped text and `<span class="hljs-*">`.
+#[server]
+pub async fn highlight_code(
+    code: String,
+    lang: String,
+) -> Result<HighlightedCode, ServerFnError> {
+    if code.len() > HIGHLIGHT_MAX_SIZE {
+        return Err(ServerFnError::Args(format!(
+            "code is {} bytes, exceeding the limit of {HIGHLIGHT_MAX_SIZE}",
+            code.len()
+        )));
+    }
+    let highlighted = tokio::task::spawn_blocking(move || {
+        crate::highlight::highlight(&code, &lang)
+    })
+    .await?;
+    Ok(HighlightedCode {
+        html: highlighted.html,
+        classes: highlighted
+            .classes
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
+    })
+}
+
+/// The outcome of compiling and running a snippet through `run_code`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunOutput {
+    /// Whether the snippet compiled, i.e. whether the rest is the output of
+    /// the snippet rather than of the compiler.
+    pub compiled: bool,
+    pub stdout: String,
+    pub stderr: String,
+    /// The exit code, if the process exited rather than being killed.
+    pub status: Option<i32>,
+    /// The signal the process was killed with, if any.
+    pub signal: Option<i32>,
+    /// Whether the process was killed for running out of time.
+    pub timed_out: bool,
+}
+
+impl RunOutput {
+    /// A description of how the run ended.
+    pub fn summary(&self) -> String {
+        match (self.timed_out, self.compiled, self.signal, self.status) {
+            (true, _, _, _) => "Timed out".to_string(),
+            (_, false, _, _) => "Failed to compile".to_string(),
+            (_, _, Some(signal), _) => format!("Killed by signal {signal}"),
+            (_, _, _, Some(status)) => format!("Exited with status {status}"),
+            _ => "Exited".to_string(),
+        }
+    }
+}
+
+/// The largest code (in bytes) accepted by `run_code`.
+pub const RUN_MAX_SIZE: usize = 64 * 1024;
+
+/// Compile and run the Rust `code` (which must have a `fn main()`) in the
+/// sandbox on the server, using the toolchain installed there.  This is
+/// only enabled when the server is started with `RUN_CODE=1`.
+#[server]
+pub async fn run_code(code: String) -> Result<RunOutput, ServerFnError> {
+    if !crate::sandbox::enabled() {
+        return Err(ServerFnError::ServerError(
+            "running code is disabled on this server".to_string(),
+        ));
+    }
+    if code.len() > RUN_MAX_SIZE {
+        return Err(ServerFnError::Args(format!(
+            "code is {} bytes, exceeding the limit of {RUN_MAX_SIZE}",
+            code.len()
+        )));
+    }
+    crate::sandbox::run(&code)
+        .await
+        .map_err(|err| ServerFnError::ServerError(err.to_string()))
+}
+
+/// The number of lines in each of the chunks produced by `stream_code`.
+pub const STREAM_CHUNK_LINES: usize = 200;
+
+/// Stream the shown lines of the code selected by `id` (as per `fetch_code`)
+/// in chunks of `STREAM_CHUNK_LINES` lines, after skipping the first `skip`
+/// lines, such that the client may render large files progressively.  The
+/// code is read from the source as it is being streamed, rather than loaded
+/// in its entirety beforehand.
+#[server(output = StreamingText)]
+pub async fn stream_code(
+    id: String,
+    lang: Option<String>,
+    skip: usize,
+) -> Result<TextStream, ServerFnError> {
+    use crate::excerpt::{split_id, Placement, Selector};
+    use std::io::BufRead;
+
+    emulate_latency().await;
+    let (base, selection) = split_id(&id).map_err(ServerFnError::new)?;
+    let base = base.to_string();
+    let source = code_source();
+    // opened before responding, such that e.g. a missing file is reported
+    // as the error of the server fn rather than in the middle of the stream
+    let mut code = tokio::task::spawn_blocking(move || {
+        source.read(&base, lang.as_deref())
+    })
+    .await
+    .map_err(ServerFnError::new)?
+    .map_err(|err| ServerFnError::new(CodeError::from(err)))?;
+
+    let (tx, rx) = tokio::sync::mpsc::channel(1);
+    tokio::task::spawn_blocking(move || {
+        // Only Rust follows the convention of hiding lines prefixed with `# `.
+        let mut selector =
+            Selector::new(selection.as_ref(), code.lang == "rust");
+        let (mut chunk, mut chunk_lines) = (String::new(), 0);
+        let (mut line, mut n, mut shown) = (String::new(), 0, 0);
+        loop {
+            line.clear();
+            match code.reader.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => n += 1,
+                Err(err) => {
+                    let _ = tx.blocking_send(Err(ServerFnError::new(err)));
+                    return;
+                }
+            }
+            let Placement::Shown(text) = selector.place(n, &line) else {
+                continue;
+            };
+            shown += 1;
+            if shown <= skip {
+                continue;
+            }
+            chunk.push_str(&text);
+            chunk_lines += 1;
+            if chunk_lines == STREAM_CHUNK_LINES {
+                chunk_lines = 0;
+                // the client went away
+                if tx.blocking_send(Ok(std::mem::take(&mut chunk))).is_err() {
+                    return;
+                }
+            }
+        }
+        if !chunk.is_empty() {
+            let _ = tx.blocking_send(Ok(chunk));
+        }
+        if selector.start_line(n).is_none() {
+            let _ = tx.blocking_send(Err(ServerFnError::new(
+                CodeError::NotFound(format!("selection `{id}` not found")),
+            )));
+        }
+    });
+    Ok(TextStream::new(futures::stream::unfold(
+        rx,
+        |mut rx| async move { rx.recv().await.map(|chunk| (chunk, rx)) },
+    )))
+}
+
+/// Regroup the chunks of a `TextStream` such that every chunk consists of
+/// complete lines, as the chunks produced by `stream_code` may be split or
+/// merged in transit.  Any trailing text without a newline is provided as
+/// the last chunk.
+pub fn line_chunks(
+    stream: impl Stream<Item = Result<String, ServerFnError>> + Send + 'static,
+) -> impl Stream<Item = Result<String, ServerFnError>> + Send + 'static {
+    futures::stream::unfold(
+        (Box::pin(stream), String::new(), false),
+        |(mut stream, mut pending, done)| async move {
+            if done {
+                return None;
+            }
+            loop {
+                match stream.next().await {
+                    Some(Ok(chunk)) => {
+                        pending.push_str(&chunk);
+                        if let Some(end) = pending.rfind('\n') {
+                            let rest = pending.split_off(end + 1);
+                            return Some((Ok(pending), (stream, rest, false)));
+                        }
+                    }
+                    Some(Err(err)) => {
+                        return Some((Err(err), (stream, pending, true)));
+                    }
+                    None if pending.is_empty() => return None,
+                    None => {
+                        return Some((
+                            Ok(pending),
+                            (stream, String::new(), true),
+                        ));
+                    }
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_chunks_regroups_lines() {
+        let chunks = ["fn main", "() {\n    le", "t x = 1;\n}\n", "// end"]
+            .into_iter()
+            .map(|chunk| Ok(chunk.to_string()));
+        let regrouped = futures::executor::block_on(
+            line_chunks(futures::stream::iter(chunks))
+                .map(Result::unwrap)
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(
+            regrouped,
+            ["fn main() {\n", "    let x = 1;\n}\n", "// end"]
+        );
+    }
+
+    #[test]
+    fn raw_code_url_encodes_fragment() {
+        assert_eq!(raw_code_url("src/main.rs"), "/code/src/main.rs");
+        assert_eq!(
+            raw_code_url("ch05_02a#L11-L13"),
+            "/code/ch05_02a%23L11-L13"
+        );
+        assert_eq!(raw_code_url("a b?%"), "/code/a%20b%3F%25");
+    }
 }
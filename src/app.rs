@@ -1,15 +1,40 @@
 use crate::{
-    api::fetch_code,
-    consts::{CH03_05A, LEPTOS_HYDRATED},
+    api::{
+        highlight_code, line_chunks, raw_code_url, stream_code, Code, RunCode,
+        RunOutput, Segment,
+    },
+    batch::fetch_code_batched,
+    compare::{Frame, FrameReport, COMPARE_SLUG, FRAME_REPORTER_JS},
+    consts::{CH03_05A, CH05_02A, LEPTOS_HYDRATED},
+    examples::{
+        ends_section, example_at, numbers, split_ssr_mode_path, title_parts,
+        Example, ExampleRoutes, Section, SSR_MODES, SSR_MODE_PREFIX,
+    },
+    lines::{
+        editor_markup, line_count, pad_lines, split_html_lines,
+        strip_diff_markers,
+    },
+    markdown::{plain_text, Element, Node},
+    sanitize::sanitize,
+    theme::{provide_highlight_theme, HighlightTheme, ThemeSelect},
 };
+use futures::StreamExt;
 use leptos::prelude::*;
 use leptos_meta::{MetaTags, *};
 use leptos_router::{
     components::{FlatRoutes, Route, Router, A},
     path, SsrMode,
 };
+use std::ops::RangeInclusive;
 
 pub fn shell(options: LeptosOptions) -> impl IntoView {
+    // the page may be loaded as one of the frames of the comparison page
+    #[cfg(feature = "ssr")]
+    let frame = use_context::<axum::http::request::Parts>()
+        .and_then(|parts| Frame::from_query(parts.uri.query()?));
+    #[cfg(not(feature = "ssr"))]
+    let frame = None::<Frame>;
+    let hydrates = frame.is_none_or(Frame::hydrates);
     view! {
         <!DOCTYPE html>
         <html lang="en">
@@ -17,7 +42,9 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
                 <meta charset="utf-8"/>
                 <meta name="viewport" content="width=device-width, initial-scale=1"/>
                 <AutoReload options=options.clone()/>
-                <HydrationScripts options/>
+                {hydrates.then(|| view! { <HydrationScripts options/> })}
+                {hydrates.then(|| view! { <HljsModulePreload/> })}
+                {frame.map(|_| view! { <script inner_html=FRAME_REPORTER_JS></script> })}
                 <MetaTags/>
             </head>
             <body>
@@ -31,42 +58,21 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
 pub fn App() -> impl IntoView {
     // Provides context that manages stylesheets, titles, meta tags, etc.
     provide_meta_context();
+    provide_highlight_theme();
     let fallback = || view! { "Page not found." }.into_view();
-    let ssr = SsrMode::Async;
 
     view! {
         <Stylesheet id="leptos" href="/pkg/axum_js_ssr.css"/>
         <Title text="Leptos JavaScript Integration Demo with SSR in Axum"/>
         <Meta name="color-scheme" content="dark light"/>
+        <HighlightTheme/>
         <Router>
             <nav>
                 <A attr:class="section" href="/">"Introduction (home)"</A>
-                <A attr:class="example" href="/naive">"Naive "<code>"<script>"</code>
-                    <small>"truly naive to start off"</small></A>
-                <A attr:class="example" href="/naive-alt">"Leptos "<code>"<Script>"</code>
-                    <small>"naively using load event"</small></A>
-                <A attr:class="example" href="/naive-hook">"Leptos "<code>"<Script>"</code>
-                    <small>"... correcting placement"</small></A>
-                <A attr:class="example" href="/naive-fallback">"Leptos "<code>"<Script>"</code>
-                    <small>"... with fallback"</small></A>
-                <A attr:class="example" href="/signal-effect-script">"Leptos Signal + Effect"
-                    <small>"an idiomatic Leptos solution"</small></A>
-                <A attr:class="subexample section" href="/custom-event">"Hydrated Event"
-                    <small>"using "<code>"js_sys"</code>"/"<code>"web_sys"</code></small></A>
-                <A attr:class="example" href="/wasm-bindgen-naive">"Using "<code>"wasm-bindgen"</code>
-                    <small>"naively to start with"</small></A>
-                <A attr:class="example" href="/wasm-bindgen-event">"Using "<code>"wasm-bindgen"</code>
-                    <small>"overcomplication with events"</small></A>
-                <A attr:class="example" href="/wasm-bindgen-effect">"Using "<code>"wasm-bindgen"</code>
-                    <small>"lazily delay DOM manipulation"</small></A>
-                <A attr:class="example" href="/wasm-bindgen-direct">"Using "<code>"wasm-bindgen"</code>
-                    <small>"without DOM manipulation"</small></A>
-                <A attr:class="example section" href="/wasm-bindgen-direct-fixed">
-                    "Using "<code>"wasm-bindgen"</code>
-                    <small>"corrected with signal + effect"</small>
-                </A>
+                {example_links()}
                 <a id="reset" href="/" target="_self">"Restart/Rehydrate"
                     <small>"to make things work again"</small></a>
+                <ThemeSelect/>
             </nav>
             <main>
                 <div id="notice">
@@ -77,21 +83,10 @@ pub fn App() -> impl IntoView {
                 </div>
                 <article>
                     <h1>"Leptos JavaScript Integration Demo with SSR in Axum"</h1>
+                    <SsrModeSelect/>
                     <FlatRoutes fallback>
                         <Route path=path!("") view=HomePage/>
-                        <Route path=path!("naive") view=Naive ssr/>
-                        <Route path=path!("naive-alt") view=|| view! { <NaiveEvent/> } ssr/>
-                        <Route path=path!("naive-hook") view=|| view! { <NaiveEvent hook=true/> } ssr/>
-                        <Route path=path!("naive-fallback") view=|| view! {
-                            <NaiveEvent hook=true fallback=true/>
-                        } ssr/>
-                        <Route path=path!("signal-effect-script") view=CodeDemoSignalEffect ssr/>
-                        <Route path=path!("custom-event") view=CustomEvent ssr/>
-                        <Route path=path!("wasm-bindgen-naive") view=WasmBindgenNaive ssr/>
-                        <Route path=path!("wasm-bindgen-event") view=WasmBindgenJSHookReadyEvent ssr/>
-                        <Route path=path!("wasm-bindgen-effect") view=WasmBindgenEffect ssr/>
-                        <Route path=path!("wasm-bindgen-direct") view=WasmBindgenDirect ssr/>
-                        <Route path=path!("wasm-bindgen-direct-fixed") view=WasmBindgenDirectFixed ssr/>
+                        <ExampleRoutes examples=EXAMPLES/>
                     </FlatRoutes>
                 </article>
             </main>
@@ -99,6 +94,276 @@ pub fn App() -> impl IntoView {
     }
 }
 
+/// The examples, in the order they are listed and numbered in the navigation.
+pub static EXAMPLES: &[Example] = &[
+    Example {
+        slug: "naive",
+        title: "Naive `<script>`",
+        subtitle: "truly naive to start off",
+        section: Section::Script,
+        sub: false,
+        view: || view! { <Naive/> }.into_any(),
+        ssr: SsrMode::Async,
+        tags: &["script"],
+    },
+    Example {
+        slug: "naive-alt",
+        title: "Leptos `<Script>`",
+        subtitle: "naively using load event",
+        section: Section::Script,
+        sub: false,
+        view: || view! { <NaiveEvent/> }.into_any(),
+        ssr: SsrMode::Async,
+        tags: &["script", "event"],
+    },
+    Example {
+        slug: "naive-hook",
+        title: "Leptos `<Script>`",
+        subtitle: "... correcting placement",
+        section: Section::Script,
+        sub: false,
+        view: || view! { <NaiveEvent hook=true/> }.into_any(),
+        ssr: SsrMode::Async,
+        tags: &["script", "event"],
+    },
+    Example {
+        slug: "naive-fallback",
+        title: "Leptos `<Script>`",
+        subtitle: "... with fallback",
+        section: Section::Script,
+        sub: false,
+        view: || view! { <NaiveEvent hook=true fallback=true/> }.into_any(),
+        ssr: SsrMode::Async,
+        tags: &["script", "event"],
+    },
+    Example {
+        slug: "signal-effect-script",
+        title: "Leptos Signal + Effect",
+        subtitle: "an idiomatic Leptos solution",
+        section: Section::Script,
+        sub: false,
+        view: || view! { <CodeDemoSignalEffect/> }.into_any(),
+        ssr: SsrMode::Async,
+        tags: &["script", "effect"],
+    },
+    Example {
+        slug: "custom-event",
+        title: "Hydrated Event",
+        subtitle: "using `js_sys`/`web_sys`",
+        section: Section::Script,
+        sub: true,
+        view: || view! { <CustomEvent/> }.into_any(),
+        ssr: SsrMode::Async,
+        tags: &["script", "event"],
+    },
+    Example {
+        slug: "wasm-bindgen-naive",
+        title: "Using `wasm-bindgen`",
+        subtitle: "naively to start with",
+        section: Section::WasmBindgen,
+        sub: false,
+        view: || view! { <WasmBindgenNaive/> }.into_any(),
+        ssr: SsrMode::Async,
+        tags: &["wasm-bindgen"],
+    },
+    Example {
+        slug: "wasm-bindgen-event",
+        title: "Using `wasm-bindgen`",
+        subtitle: "overcomplication with events",
+        section: Section::WasmBindgen,
+        sub: false,
+        view: || view! { <WasmBindgenJSHookReadyEvent/> }.into_any(),
+        ssr: SsrMode::Async,
+        tags: &["wasm-bindgen", "event"],
+    },
+    Example {
+        slug: "wasm-bindgen-effect",
+        title: "Using `wasm-bindgen`",
+        subtitle: "lazily delay DOM manipulation",
+        section: Section::WasmBindgen,
+        sub: false,
+        view: || view! { <WasmBindgenEffect/> }.into_any(),
+        ssr: SsrMode::Async,
+        tags: &["wasm-bindgen", "effect"],
+    },
+    Example {
+        slug: "wasm-bindgen-direct",
+        title: "Using `wasm-bindgen`",
+        subtitle: "without DOM manipulation",
+        section: Section::WasmBindgen,
+        sub: false,
+        view: || view! { <WasmBindgenDirect/> }.into_any(),
+        ssr: SsrMode::Async,
+        tags: &["wasm-bindgen"],
+    },
+    Example {
+        slug: "wasm-bindgen-direct-fixed",
+        title: "Using `wasm-bindgen`",
+        subtitle: "corrected with signal + effect",
+        section: Section::WasmBindgen,
+        sub: false,
+        view: || view! { <WasmBindgenDirectFixed/> }.into_any(),
+        ssr: SsrMode::Async,
+        tags: &["wasm-bindgen", "effect"],
+    },
+    Example {
+        slug: "streaming",
+        title: "Streaming code",
+        subtitle: "progressively rendered via server fn",
+        section: Section::Features,
+        sub: false,
+        view: || view! { <StreamingCode/> }.into_any(),
+        ssr: SsrMode::OutOfOrder,
+        tags: &["server-fn", "streaming"],
+    },
+    Example {
+        slug: "editor",
+        title: "Live code editor",
+        subtitle: "re-highlighted as it is edited",
+        section: Section::Features,
+        sub: false,
+        view: || view! { <EditorDemo/> }.into_any(),
+        ssr: SsrMode::OutOfOrder,
+        tags: &["wasm-bindgen", "editor"],
+    },
+    Example {
+        slug: "markdown",
+        title: "Markdown",
+        subtitle: "with code blocks through CodeInner",
+        section: Section::Features,
+        sub: false,
+        view: || view! { <MarkdownDemo/> }.into_any(),
+        ssr: SsrMode::OutOfOrder,
+        tags: &["markdown"],
+    },
+    Example {
+        slug: COMPARE_SLUG,
+        title: "Comparison",
+        subtitle: "SSR, CSR and without WASM side by side",
+        section: Section::Features,
+        sub: false,
+        view: || view! { <CompareDemo/> }.into_any(),
+        ssr: SsrMode::OutOfOrder,
+        tags: &["compare"],
+    },
+    Example {
+        slug: "remote-highlight",
+        title: "Server-side highlighting",
+        subtitle: "without running highlight.js",
+        section: Section::Features,
+        sub: false,
+        view: || view! { <RemoteHighlightDemo/> }.into_any(),
+        ssr: SsrMode::OutOfOrder,
+        tags: &["server-fn"],
+    },
+];
+
+fn example_links() -> impl IntoView {
+    let location = leptos_router::hooks::use_location();
+    let title = |title: &'static str| {
+        title_parts(title)
+            .map(|(code, part)| {
+                if code {
+                    view! { <code>{part}</code> }.into_any()
+                } else {
+                    part.into_any()
+                }
+            })
+            .collect_view()
+    };
+    EXAMPLES
+        .iter()
+        .zip(numbers(EXAMPLES))
+        .enumerate()
+        .map(|(index, (example, number))| {
+            let mut class = match (&number, example.sub) {
+                (None, _) => vec![],
+                (Some(_), false) => vec!["example"],
+                (Some(_), true) => vec!["subexample"],
+            };
+            if ends_section(EXAMPLES, index) {
+                class.push("section");
+            }
+            let number = number.map(|number| {
+                let sep = if example.sub { " " } else { ". " };
+                view! { <span class="number">{number}{sep}</span> }
+            });
+            let slug = example.slug;
+            // staying with the SSR mode the current example is under, such
+            // that the link of the example is current whichever mode it is
+            let href = move || {
+                location
+                    .pathname
+                    .with(|path| match split_ssr_mode_path(path) {
+                        Some((mode, _)) => {
+                            format!("/{SSR_MODE_PREFIX}/{mode}/{slug}")
+                        }
+                        None => format!("/{slug}"),
+                    })
+            };
+            view! {
+                <A
+                    attr:class=(!class.is_empty()).then(|| class.join(" "))
+                    attr:data-tags=example.tags.join(" ")
+                    href=href
+                >
+                    {number}
+                    {title(example.title)}
+                    <small>{title(example.subtitle)}</small>
+                </A>
+            }
+        })
+        .collect_view()
+}
+
+/// The SSR mode the current example was rendered with, along with the links
+/// to have it rendered again with each of the modes.  These are full page
+/// loads, as the mode only matters for the response from the server, and
+/// so this is only shown until the first client-side navigation.
+#[component]
+fn SsrModeSelect() -> impl IntoView {
+    let location = leptos_router::hooks::use_location();
+    let loaded = location.pathname.get_untracked();
+    let navigated = RwSignal::new(false);
+    Effect::new({
+        let loaded = loaded.clone();
+        move |_| {
+            if location.pathname.with(|path| *path != loaded) {
+                navigated.set(true);
+            }
+        }
+    });
+    move || {
+        (!navigated.get()).then(|| {
+            example_at(EXAMPLES, &loaded).map(|(example, current)| {
+                let slug = example.slug;
+                let link = move |name: &'static str, label: &'static str| {
+                    view! {
+                        <a
+                            href=format!("/{slug}?ssr={name}")
+                            rel="external"
+                            aria-current=(name == current).then_some("true")
+                        >
+                            {label}
+                        </a>
+                    }
+                };
+                let modes = SSR_MODES
+                    .iter()
+                    .map(|(name, _)| view! { " " {link(name, name)} })
+                    .collect_view();
+                view! {
+                    <p id="ssr-mode">
+                        "Rendered with SSR mode "<strong>{current}</strong>
+                        "; render with"{modes}" or the "{link("default", "default")}"."
+                    </p>
+                }
+            })
+        })
+        .flatten()
+    }
+}
+
 #[component]
 fn HomePage() -> impl IntoView {
     view! {
@@ -124,9 +389,21 @@ fn HomePage() -> impl IntoView {
             off with naive examples that mimics JavaScript conventions, again with the final example of the
             group (example 10) being the fully working version that embraces the use of Rust.
         "</p>
+        <p>"
+            The code examples are loaded through the "<code>"fetch_code"</code>" server function, which may
+            also provide an excerpt by a line range or a named region, such as the "<code>"area"</code>"
+            function from the example used throughout (with the rest of the lines collapsed):
+        "</p>
+        <CodeExcerpt id=format!("{CODE_ID}#L11-L13") lang="rust" highlight_lines=vec![12..=12]/>
     }
 }
 
+// The id of the code example loaded via `fetch_code`.
+const CODE_ID: &str = "ch05_02a";
+
+// The id of the inline example in the source, for its copy button to link to.
+const INLINE_CODE_ID: &str = "ch03_05a";
+
 #[derive(Clone, Debug)]
 struct CodeDemoHook {
     js_hook: String,
@@ -134,7 +411,10 @@ struct CodeDemoHook {
 
 #[component]
 fn CodeDemo() -> impl IntoView {
-    let code = Resource::new(|| (), |_| fetch_code());
+    let code = Resource::new(
+        || (),
+        |_| fetch_code_batched(CODE_ID.to_string(), Some("rust".to_string())),
+    );
     let code_view = move || {
         Suspend::new(async move {
             let hook = use_context::<CodeDemoHook>().map(|h| {
@@ -144,7 +424,9 @@ fn CodeDemo() -> impl IntoView {
                 }
             });
             view! {
-                <pre><code class="language-rust">{code.await}</code></pre>
+                {code.await.map(|code| view! {
+                    <pre><code class="language-rust">{code.source}</code></pre>
+                })}
                 {hook}
             }
         })
@@ -161,8 +443,12 @@ fn CodeDemo() -> impl IntoView {
                 </thead>
                 <tbody>
                     <tr>
-                        <td><pre><code class="language-rust">{CH03_05A}</code></pre></td>
                         <td>
+                            <CopyButton source_id=Some(INLINE_CODE_ID.to_string())/>
+                            <pre><code class="language-rust">{inline_example()}</code></pre>
+                        </td>
+                        <td>
+                            <CopyButton source_id=Some(CODE_ID.to_string())/>
                             <Suspense fallback=move || view! { <p>"Loading code example..."</p> }>
                                 {code_view}
                             </Suspense>
@@ -174,10 +460,17 @@ fn CodeDemo() -> impl IntoView {
     }
 }
 
+// The example that is part of the components, padded to the length of the
+// one loaded through `fetch_code` next to it.
+fn inline_example() -> String {
+    pad_lines(CH03_05A, line_count(CH05_02A))
+}
+
 #[component]
 fn Naive() -> impl IntoView {
     let loader = r#"<script src="/highlight.min.js"></script>
 <script>hljs.highlightAll();</script>"#;
+    let (integrity, crossorigin) = sri_attrs().unzip();
     view! {
         <h2>"Showing what happens when script inclusion is done naively"</h2>
         <CodeDemo/>
@@ -261,11 +554,60 @@ fn Naive() -> impl IntoView {
                 exercise for the users and readers of this demo application.
             "</li>
         </ol>
-        <script src="/highlight.min.js"></script>
+        <script src="/highlight.min.js" integrity=integrity crossorigin=crossorigin></script>
         <script>"hljs.highlightAll();"</script>
     }
 }
 
+// The `integrity` and `crossorigin` attributes for loading the standard
+// `highlight.js`, which are only left out when SRI is switched off for
+// development (through the `no-sri` feature), as the latency middleware then
+// modifies the script.
+fn sri_attrs() -> Option<(&'static str, &'static str)> {
+    (!cfg!(feature = "no-sri"))
+        .then_some((crate::consts::HIGHLIGHT_MIN_JS_SRI, "anonymous"))
+}
+
+// The ES module build of `highlight.js` is imported by the bundle, where no
+// integrity may be given, so it is preloaded with one instead: the import
+// then makes use of the preloaded module, and fails along with it.
+#[component]
+fn HljsModulePreload() -> impl IntoView {
+    #[cfg(feature = "ssr")]
+    let url = use_context::<crate::assets::HljsModuleUrl>()
+        .filter(|_| !cfg!(feature = "no-sri"))
+        .map(|url| url.0);
+    #[cfg(not(feature = "ssr"))]
+    let url = None::<String>;
+    url.map(|href| {
+        view! {
+            <link
+                rel="modulepreload"
+                href=href
+                integrity=crate::consts::HIGHLIGHT_ES_MIN_JS_SRI
+                crossorigin="anonymous"
+            />
+        }
+    })
+}
+
+// The `<Script>` for the asynchronous loading of the standard `highlight.js`
+// used by the examples, with the SRI attributes applied where applicable.
+#[component]
+fn HljsScript() -> impl IntoView {
+    // FIXME Seems like <Script> require a text node, otherwise hydration error from marker mismatch
+    match sri_attrs() {
+        Some((integrity, crossorigin)) => view! {
+            <Script id="hljs-src" async_="true" src="/highlight.min.js" integrity crossorigin>""</Script>
+        }
+        .into_any(),
+        None => view! {
+            <Script id="hljs-src" async_="true" src="/highlight.min.js">""</Script>
+        }
+        .into_any(),
+    }
+}
+
 #[component]
 fn NaiveEvent(
     #[prop(optional)] hook: bool,
@@ -353,9 +695,12 @@ if (window.hljs) {
                     uncertainty of the order of events, it may or may not result in the dynamic code block (or
                     any) being highlighted under CSR (as there may or may not be a fully formed code block for
                     highlighting to happen).  This is affected by latency, so the loader here emulates a small
-                    number of latency values (they repeat in a cycle).  The latency value is logged into the
-                    console and it may be referred to witness its effects on what it does under CSR - look for
-                    the line that might say \"loaded standard highlight.js with a minimum latency of 40 ms\".
+                    number of latency values (they repeat in a cycle).  The latency value is reported as the
+                    "<code>"injected-latency"</code>" of the "<code>"Server-Timing"</code>" of the request for
+                    "<code>"highlight.min.js"</code>" (see the network tab of the browser's developer tools) and
+                    it may be referred to witness its effects on what it does under CSR.  When built with the
+                    "<code>"no-sri"</code>" feature, it is also logged into the console - look for the line
+                    that might say \"loaded standard highlight.js with a minimum latency of 40 ms\".
                     Test this by going from home to here and then navigating between them using the browser's
                     back and forward feature for convenience - do ensure the "<code>"highlight.js" </code>"
                     isn't being cached by the browser.
@@ -371,11 +716,10 @@ if (window.hljs) {
             </ol>
         }.into_any()
     };
-    // FIXME Seems like <Script> require a text node, otherwise hydration error from marker mismatch
     view! {
         <h2>"Using the Leptos "<code>"<Script>"</code>" component asynchronously instead"</h2>
         <CodeDemo/>
-        <Script id="hljs-src" async_="true" src="/highlight.min.js">""</Script>
+        <HljsScript/>
         // Example 2's <Script> invocation; Example 3 and 4 will be provided via a context to allow the
         // inclusion of the `highlightAll()` call in the Suspend
         {(!hook).then(|| view! { <Script>{render_hook}</Script>})}
@@ -418,11 +762,10 @@ Promise.all(events).then(() => {{
     provide_context(CodeDemoHook {
         js_hook: js_hook.clone(),
     });
-    // FIXME Seems like <Script> require a text node, otherwise hydration error from marker mismatch
     view! {
         <h2>"Have Leptos dispatch an event when body is hydrated"</h2>
         <CodeDemo/>
-        <Script id="hljs-src" async_="true" src="/highlight.min.js">""</Script>
+        <HljsScript/>
         <p>"
             So if using events fixes problems with timing issues, couldn't Leptos provide an event to signal
             that the body is hydrated?  Well, this problem is typically solved by having a signal in the
@@ -502,7 +845,10 @@ if (window.hljs) {
             console.log('using hljs inside addEventListener; leaving hljs loaded');
         }, false);
 };"#;
-    let code = Resource::new(|| (), |_| fetch_code());
+    let code = Resource::new(
+        || (),
+        |_| fetch_code_batched(CODE_ID.to_string(), Some("rust".to_string())),
+    );
     let (script, set_script) = signal(None::<String>);
     let code_view = move || {
         Suspend::new(async move {
@@ -510,7 +856,9 @@ if (window.hljs) {
                 set_script.set(Some(render_call.to_string()));
             });
             view! {
-                <pre><code class="language-rust">{code.await}</code></pre>
+                {code.await.map(|code| view! {
+                    <pre><code class="language-rust">{code.source}</code></pre>
+                })}
                 {
                     move || script.get().map(|script| {
                         view! { <Script>{script}</Script> }
@@ -520,7 +868,7 @@ if (window.hljs) {
         })
     };
     view! {
-        <Script id="hljs-src" async_="true" src="/highlight.min.js">""</Script>
+        <HljsScript/>
         <h2>"Using signal + effect to dynamically set "<code>"<Script>"</code>" tag as view is mounted"</h2>
         <p>"Explanation on what is being demonstrated follows after the following code example table."</p>
         <div id="code-demo">
@@ -533,8 +881,12 @@ if (window.hljs) {
                 </thead>
                 <tbody>
                     <tr>
-                        <td><pre><code class="language-rust">{CH03_05A}</code></pre></td>
                         <td>
+                            <CopyButton source_id=Some(INLINE_CODE_ID.to_string())/>
+                            <pre><code class="language-rust">{inline_example()}</code></pre>
+                        </td>
+                        <td>
+                            <CopyButton source_id=Some(CODE_ID.to_string())/>
                             <Suspense fallback=move || view! { <p>"Loading code example..."</p> }>
                                 {code_view}
                             </Suspense>
@@ -559,7 +911,7 @@ if (window.hljs) {
     document.querySelector('#hljs-src')
         .addEventListener('load', (e) => { hljs.highlightAll() }, false);
 };"#;
-    let code = Resource::new(|| (), |_| fetch_code());
+    let code = Resource::new(|| (), |_| fetch_code("ch05_02a".to_string(), None));
     let (script, set_script) = signal(None::<String>);
     let code_view = move || {
         Suspend::new(async move {
@@ -567,7 +919,7 @@ if (window.hljs) {
                 set_script.set(Some(render_call.to_string()));
             });
             view! {
-                <pre><code class="language-rust">{code.await}</code></pre>
+                <pre><code class="language-rust">{code.await.map(|code| code.source)}</code></pre>
                 {
                     move || script.get().map(|script| {
                         view! { <Script>{script}</Script> }
@@ -610,13 +962,18 @@ enum WasmDemo {
 
 #[component]
 fn CodeDemoWasm(mode: WasmDemo) -> impl IntoView {
-    let code = Resource::new(|| (), |_| fetch_code());
+    let code = Resource::new(
+        || (),
+        |_| fetch_code_batched(CODE_ID.to_string(), Some("rust".to_string())),
+    );
     let suspense_choice = match mode {
         WasmDemo::Naive => view! {
             <Suspense fallback=move || view! { <p>"Loading code example..."</p> }>{
                 move || Suspend::new(async move {
                     view! {
-                        <pre><code class="language-rust">{code.await}</code></pre>
+                        {code.await.map(|code| view! {
+                            <pre><code class="language-rust">{code.source}</code></pre>
+                        })}
                         {
                             #[cfg(not(feature = "ssr"))]
                             {
@@ -633,7 +990,9 @@ fn CodeDemoWasm(mode: WasmDemo) -> impl IntoView {
             <Suspense fallback=move || view! { <p>"Loading code example..."</p> }>{
                 move || Suspend::new(async move {
                     view! {
-                        <pre><code class="language-rust">{code.await}</code></pre>
+                        {code.await.map(|code| view! {
+                            <pre><code class="language-rust">{code.source}</code></pre>
+                        })}
                         {
                             #[cfg(not(feature = "ssr"))]
                             {
@@ -702,7 +1061,9 @@ fn CodeDemoWasm(mode: WasmDemo) -> impl IntoView {
                         });
                     });
                     view! {
-                        <pre><code class="language-rust">{code.await}</code></pre>
+                        {code.await.map(|code| view! {
+                            <pre><code class="language-rust">{code.source}</code></pre>
+                        })}
                     }
                 })
             }</Suspense>
@@ -726,8 +1087,11 @@ fn CodeDemoWasm(mode: WasmDemo) -> impl IntoView {
                 </thead>
                 <tbody>
                     <tr>
-                        <td><pre><code class="language-rust">{CH03_05A}</code></pre></td>
-                        <td>{suspense_choice}</td>
+                        <td>
+                            <CopyButton source_id=Some(INLINE_CODE_ID.to_string())/>
+                            <pre><code class="language-rust">{inline_example()}</code></pre>
+                        </td>
+                        <td><CopyButton source_id=Some(CODE_ID.to_string())/>{suspense_choice}</td>
                     </tr>
                 </tbody>
             </table>
@@ -740,7 +1104,7 @@ fn WasmBindgenNaive() -> impl IntoView {
     let example = r#"<Suspense fallback=move || view! { <p>"Loading code example..."</p> }>{
     move || Suspend::new(async move {
         view! {
-            <pre><code>{code.await}</code></pre>
+            <pre><code>{code.await.map(|code| code.source)}</code></pre>
             {
                 #[cfg(not(feature = "ssr"))]
                 {
@@ -878,7 +1242,7 @@ fn WasmBindgenEffect() -> impl IntoView {
             });
         });
         view! {
-            <pre><code>{code.await}</code></pre>
+            <pre><code>{code.await.map(|code| code.source)}</code></pre>
         }
     })
 }</Suspense>"#;
@@ -915,17 +1279,78 @@ fn WasmBindgenEffect() -> impl IntoView {
 #[derive(Clone)]
 struct InnerEffect;
 
+// Have `CodeInner` use the markup from the `highlight_code` server function
+// rather than running highlight.js on the client.
+#[derive(Clone)]
+struct RemoteHighlight;
+
+// The code block along with a button to copy the code.
 #[component]
-fn CodeInner(code: String, lang: String) -> impl IntoView {
+fn CodeInner(
+    code: String,
+    lang: String,
+    #[prop(optional)] line_numbers: bool,
+    #[prop(optional)] highlight_lines: Vec<RangeInclusive<usize>>,
+    #[prop(default = 1)] start_line: usize,
+    #[prop(optional)] diff: bool,
+    /// Provide a button to run the (Rust) code on the server.
+    #[prop(optional)]
+    runnable: bool,
+    /// The id the code was loaded by from the source, if it was, for the copy
+    /// button to link to.
+    #[prop(optional, into)]
+    source_id: Option<String>,
+) -> impl IntoView {
+    let run = (runnable && lang == "rust").then(ServerAction::<RunCode>::new);
+    view! {
+        <div class="code-block">
+            <CopyButton source_id/>
+            {run.map(|run| view! { <RunButton run code=code.clone()/> })}
+            <CodeBlock code lang line_numbers highlight_lines start_line diff/>
+            {run.map(|run| view! { <RunResult run/> })}
+        </div>
+    }
+}
+
+#[component]
+fn CodeBlock(
+    code: String,
+    lang: String,
+    /// Show the line numbers in a gutter.
+    #[prop(optional)]
+    line_numbers: bool,
+    /// The (numbered as shown) lines to be highlighted.
+    #[prop(optional)]
+    highlight_lines: Vec<RangeInclusive<usize>>,
+    /// The number of the first line.
+    #[prop(default = 1)]
+    start_line: usize,
+    /// Treat the code as a diff, styling the lines prefixed by `+`/`-` as
+    /// added/removed.
+    #[prop(optional)]
+    diff: bool,
+) -> impl IntoView {
+    if line_numbers || diff || !highlight_lines.is_empty() {
+        return view! {
+            <CodeLines code lang line_numbers highlight_lines start_line diff/>
+        }
+        .into_any();
+    }
+    if use_context::<RemoteHighlight>().is_some() {
+        return view! { <RemoteCode code lang/> }.into_any();
+    }
     // lang is currently unused for SSR, so just drop it now to use it to avoid warning.
     #[cfg(feature = "ssr")]
     drop(lang);
     if use_context::<InnerEffect>().is_none() {
+        // All markup passes through `sanitize` before being used as
+        // `inner_html`, as the code may come from anywhere.
         #[cfg(feature = "ssr")]
-        let inner = Some(html_escape::encode_text(&code).into_owned());
+        let inner = Some(sanitize(&html_escape::encode_text(&code)));
         #[cfg(not(feature = "ssr"))]
         let inner = {
-            let inner = crate::hljs::highlight(code, lang);
+            let inner =
+                crate::hljs::highlight(code, lang).map(|html| sanitize(&html));
             leptos::logging::log!(
                 "about to populate inner_html with: {inner:?}"
             );
@@ -939,12 +1364,13 @@ fn CodeInner(code: String, lang: String) -> impl IntoView {
         let (inner, set_inner) = signal(String::new());
         #[cfg(feature = "ssr")]
         {
-            set_inner.set(html_escape::encode_text(&code).into_owned());
+            set_inner.set(sanitize(&html_escape::encode_text(&code)));
         };
         #[cfg(not(feature = "ssr"))]
         {
             leptos::logging::log!("calling out to hljs::highlight");
-            let result = crate::hljs::highlight(code, lang);
+            let result =
+                crate::hljs::highlight(code, lang).map(|html| sanitize(&html));
             Effect::new(move |_| {
                 leptos::logging::log!(
                     "setting the result of hljs::highlight inside an effect"
@@ -961,14 +1387,241 @@ fn CodeInner(code: String, lang: String) -> impl IntoView {
     }
 }
 
+// Render the code line by line, with the structure derived from the plain
+// code alone such that it is identical between SSR and hydration.  Much like
+// `CodeInner` with `InnerEffect`, the lines start off as the escaped code,
+// with the highlighted lines swapped in by an effect on the client.
+#[component]
+fn CodeLines(
+    code: String,
+    lang: String,
+    line_numbers: bool,
+    highlight_lines: Vec<RangeInclusive<usize>>,
+    start_line: usize,
+    diff: bool,
+) -> impl IntoView {
+    let (markers, code) = if diff {
+        let (markers, code) = strip_diff_markers(&code);
+        (Some(markers), code)
+    } else {
+        (None, code)
+    };
+    let count = line_count(&code);
+    let (html_lines, set_html_lines) = signal(split_html_lines(
+        &sanitize(&html_escape::encode_text(&code)),
+        count,
+    ));
+    // only highlighting through the server may fail to be reported
+    let (error, set_error) = signal(None::<String>);
+    #[cfg(feature = "ssr")]
+    let _ = (lang, set_html_lines, set_error);
+    #[cfg(not(feature = "ssr"))]
+    {
+        let remote = use_context::<RemoteHighlight>().is_some();
+        Effect::new(move |_| {
+            let (code, lang) = (code.clone(), lang.clone());
+            if remote {
+                leptos::task::spawn_local(async move {
+                    match highlight_code(code, lang).await {
+                        Ok(highlighted) => {
+                            set_html_lines.set(split_html_lines(
+                                &sanitize(&highlighted.html),
+                                count,
+                            ))
+                        }
+                        Err(err) => set_error.set(Some(err.to_string())),
+                    }
+                });
+            } else if let Some(html) = crate::hljs::highlight(code, lang) {
+                set_html_lines.set(split_html_lines(&sanitize(&html), count));
+            }
+        });
+    }
+    let lines = (0..count)
+        .map(|i| {
+            let number = start_line + i;
+            let marker = markers.as_ref().map(|markers| markers[i]);
+            let mut class = String::from("line");
+            if highlight_lines.iter().any(|range| range.contains(&number)) {
+                class.push_str(" highlighted");
+            }
+            if let Some(marker) = marker {
+                class.push(' ');
+                class.push_str(marker.class());
+            }
+            let content = move || {
+                html_lines.with(|lines| lines.get(i).cloned().unwrap_or_default())
+            };
+            view! {
+                <span class=class>
+                    {line_numbers.then(|| view! { <span class="line-number">{number}</span> })}
+                    {marker.map(|marker| view! { <span class="diff-marker">{marker.symbol()}</span> })}
+                    <span class="line-content" inner_html=content></span>
+                </span>
+            }
+        })
+        .collect_view();
+    view! {
+        <pre class="code-lines"><code>{lines}</code></pre>
+        {move || error.get().map(|err| view! { <p class="error">{err}</p> })}
+    }
+}
+
+// Only the client ever gets to copy.
+#[cfg_attr(feature = "ssr", allow(dead_code))]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CopyState {
+    Idle,
+    Copied,
+    Failed,
+}
+
+/// A button to copy the code shown along with it, which is read from the
+/// page when clicked (see `clipboard::code_text`), so the button has to be
+/// placed in the same parent element as the code.
+///
+/// Where the code was loaded from the source by its `source_id`, the button
+/// is a link to download it (see `api::raw_code_url`), such that it works
+/// without a (working) client.  Once hydrated the clicks copy the code
+/// instead, unless copying has failed in which case the link is left to
+/// download the code.
+#[component]
+fn CopyButton(
+    #[prop(optional_no_strip)] source_id: Option<String>,
+) -> impl IntoView {
+    let state = RwSignal::new(CopyState::Idle);
+    let linked = source_id.is_some();
+    let on_click = move |ev: leptos::ev::MouseEvent| {
+        if linked && state.get_untracked() == CopyState::Failed {
+            return;
+        }
+        ev.prevent_default();
+        #[cfg(not(feature = "ssr"))]
+        {
+            use wasm_bindgen::JsCast;
+            let code = ev
+                .current_target()
+                .and_then(|target| target.dyn_into::<web_sys::Element>().ok())
+                .and_then(|button| crate::clipboard::code_text(&button));
+            leptos::task::spawn_local(async move {
+                let copied = match code {
+                    Some(code) => crate::clipboard::copy_text(&code).await,
+                    None => false,
+                };
+                state.set(if copied {
+                    CopyState::Copied
+                } else {
+                    CopyState::Failed
+                });
+                // the link is left to download the code after a failure
+                if copied || !linked {
+                    set_timeout(
+                        move || state.set(CopyState::Idle),
+                        std::time::Duration::from_secs(2),
+                    );
+                }
+            });
+        }
+    };
+    let label = move || match state.get() {
+        CopyState::Idle => "Copy",
+        CopyState::Copied => "Copied",
+        CopyState::Failed if linked => "Download",
+        CopyState::Failed => "Copy failed",
+    };
+    match source_id {
+        Some(id) => view! {
+            <a class="copy-button" role="button" href=raw_code_url(&id) download on:click=on_click>
+                {label}
+            </a>
+        }
+        .into_any(),
+        None => view! {
+            <button class="copy-button" type="button" on:click=on_click>
+                {label}
+            </button>
+        }
+        .into_any(),
+    }
+}
+
+#[component]
+fn RunButton(run: ServerAction<RunCode>, code: String) -> impl IntoView {
+    let pending = run.pending();
+    view! {
+        <button
+            class="run-button"
+            disabled=pending
+            on:click=move |_| {
+                run.dispatch(RunCode { code: code.clone() });
+            }
+        >
+            {move || if pending.get() { "Running..." } else { "Run" }}
+        </button>
+    }
+}
+
+#[component]
+fn RunResult(run: ServerAction<RunCode>) -> impl IntoView {
+    move || {
+        run.value().get().map(|result| match result {
+            Ok(output) => {
+                let summary = output.summary();
+                let RunOutput { stdout, stderr, .. } = output;
+                view! {
+                    <div class="run-output">
+                        <p class="run-status">{summary}</p>
+                        {(!stdout.is_empty()).then(|| view! { <pre class="stdout">{stdout}</pre> })}
+                        {(!stderr.is_empty()).then(|| view! { <pre class="stderr">{stderr}</pre> })}
+                    </div>
+                }
+                .into_any()
+            }
+            Err(err) => view! {
+                <div class="run-output">
+                    <p class="run-status">"Unable to run the code: "{err.to_string()}</p>
+                </div>
+            }
+            .into_any(),
+        })
+    }
+}
+
+// As the highlighted markup is provided through a resource, it is identical
+// between SSR and CSR and so hydration is never an issue.
+#[component]
+fn RemoteCode(code: String, lang: String) -> impl IntoView {
+    let highlighted = Resource::new(
+        move || (code.clone(), lang.clone()),
+        |(code, lang)| highlight_code(code, lang),
+    );
+    let code_view = move || {
+        Suspend::new(async move {
+            highlighted.await.map(|highlighted| {
+                view! {
+                    <pre><code class="hljs" inner_html=sanitize(&highlighted.html)></code></pre>
+                }
+            })
+        })
+    };
+    view! {
+        <Suspense fallback=move || view! { <p>"Highlighting code..."</p> }>
+            {code_view}
+        </Suspense>
+    }
+}
+
 #[component]
 fn CodeDemoWasmInner() -> impl IntoView {
-    let code = Resource::new(|| (), |_| fetch_code());
+    let code = Resource::new(
+        || (),
+        |_| fetch_code_batched(CODE_ID.to_string(), Some("rust".to_string())),
+    );
     let code_view = move || {
         Suspend::new(async move {
-            code.await.map(|code| {
+            code.await.map(|Code { source, lang, .. }| {
                 view! {
-                    <CodeInner code=code lang="rust".to_string()/>
+                    <CodeInner code=source lang runnable=true source_id=CODE_ID/>
                 }
             })
         })
@@ -989,7 +1642,7 @@ fn CodeDemoWasmInner() -> impl IntoView {
                 </thead>
                 <tbody>
                     <tr>
-                        <td><CodeInner code=CH03_05A.to_string() lang="rust".to_string()/></td>
+                        <td><CodeInner code=inline_example() lang="rust".to_string() runnable=true source_id=INLINE_CODE_ID/></td>
                         <td>
                             <Suspense fallback=move || view! { <p>"Loading code example..."</p> }>
                                 {code_view}
@@ -1132,3 +1785,539 @@ fn CodeInner(code: String, lang: String) -> impl IntoView {
         "</p>
     }
 }
+
+/// Render the excerpt of the code fetched by `id` (see `fetch_code` for the
+/// selection of lines through its fragment) numbered as per the original
+/// code, with the hidden lines collapsed into a `<details>` that may be
+/// expanded.
+#[component]
+pub fn CodeExcerpt(
+    id: String,
+    #[prop(into)] lang: String,
+    /// The lines to be highlighted, going by the original line numbers.
+    #[prop(optional)]
+    highlight_lines: Vec<RangeInclusive<usize>>,
+) -> impl IntoView {
+    let code = Resource::new(
+        {
+            let id = id.clone();
+            move || (id.clone(), lang.clone())
+        },
+        |(id, lang)| fetch_code_batched(id, Some(lang)),
+    );
+    let excerpt = move || {
+        let (id, highlight_lines) = (id.clone(), highlight_lines.clone());
+        Suspend::new(async move {
+            code.await.map(|code| {
+                let class = format!("language-{}", code.lang);
+                // the hidden lines before the first shown line are not
+                // numbered, but the ones after are
+                let mut next_line = None::<usize>;
+                code.segments
+                    .into_iter()
+                    .map(|segment| match segment {
+                        Segment::Shown(source) => {
+                            let start_line =
+                                *next_line.get_or_insert(code.start_line);
+                            next_line = Some(start_line + line_count(&source));
+                            view! {
+                                <CodeInner code=source lang=code.lang.clone()
+                                    line_numbers=true start_line source_id=id.clone()
+                                    highlight_lines=highlight_lines.clone()/>
+                            }
+                            .into_any()
+                        }
+                        Segment::Hidden(source) => {
+                            if let Some(line) = next_line.as_mut() {
+                                *line += line_count(&source);
+                            }
+                            let summary = match line_count(&source) {
+                                1 => "1 hidden line".to_string(),
+                                n => format!("{n} hidden lines"),
+                            };
+                            view! {
+                                <details class="hidden-lines">
+                                    <summary>{summary}</summary>
+                                    <pre><code class=class.clone()>{source}</code></pre>
+                                </details>
+                            }
+                            .into_any()
+                        }
+                    })
+                    .collect_view()
+            })
+        })
+    };
+    view! {
+        <div class="code-excerpt">
+            <Suspense fallback=move || view! { <p>"Loading code excerpt..."</p> }>
+                {excerpt}
+            </Suspense>
+        </div>
+    }
+}
+
+#[component]
+fn EditorDemo() -> impl IntoView {
+    let source = RwSignal::new(CH05_02A.to_string());
+    let lines = move || source.with(|source| source.lines().count());
+    view! {
+        <h2>"Editing code with live highlighting"</h2>
+        <p>"
+            The following code may be edited, with the highlighting updated as it is being typed.  This is
+            done with a "<code>"<textarea>"</code>" with transparent text laid over the highlighted code.
+            The server renders the code as plain text, which is highlighted by "<code>"highlight.js"</code>"
+            once hydrated (which is also when the editing becomes available), such that the same highlighter
+            is used throughout rather than the style of the highlighting changing on the first edit.
+        "</p>
+        <CodeEditor source lang="rust"/>
+        <p>"The code above is currently "{lines}" lines long."</p>
+    }
+}
+
+/// An editor for the `source`, highlighting it as the `lang` while it is
+/// edited by laying a `<textarea>` with transparent text over the highlighted
+/// markup.
+///
+/// All the highlighting is done by highlight.js, such that it doesn't change
+/// style on the first edit: the server renders the source as plain text with
+/// the `<textarea>` kept read-only until hydrated, after which the source is
+/// highlighted on the client, and again (debounced) as it changes.
+#[component]
+pub fn CodeEditor(
+    source: RwSignal<String>,
+    #[prop(into)] lang: String,
+) -> impl IntoView {
+    // the source as last highlighted on the client, along with its markup
+    let highlighted = RwSignal::new(None::<(String, String)>);
+    let hydrated = RwSignal::new(false);
+    let textarea = NodeRef::<leptos::html::Textarea>::new();
+    let pre = NodeRef::<leptos::html::Pre>::new();
+    #[cfg(feature = "ssr")]
+    let _ = (lang, highlighted, textarea);
+    #[cfg(not(feature = "ssr"))]
+    {
+        use leptos::leptos_dom::helpers::TimeoutHandle;
+        const DEBOUNCE: std::time::Duration =
+            std::time::Duration::from_millis(150);
+
+        Effect::new(move |_| hydrated.set(true));
+        let pending = StoredValue::new(None::<TimeoutHandle>);
+        Effect::new(move |first_run: Option<()>| {
+            let code = source.get();
+            // the source may be set from elsewhere, in which case the
+            // textarea has to be updated while keeping the cursor in place
+            if let Some(textarea) = textarea.get_untracked() {
+                if textarea.value() != code {
+                    let start = textarea.selection_start().ok().flatten();
+                    let end = textarea.selection_end().ok().flatten();
+                    textarea.set_value(&code);
+                    let len = code.encode_utf16().count() as u32;
+                    let _ = textarea.set_selection_range(
+                        start.unwrap_or(len).min(len),
+                        end.unwrap_or(len).min(len),
+                    );
+                }
+            }
+            if let Some(handle) = pending.get_value() {
+                handle.clear();
+            }
+            let lang = lang.clone();
+            // the code is mid-edit more often than not
+            let highlight = move || {
+                highlighted.set(
+                    crate::hljs::highlight_ignore_illegals(code.clone(), lang)
+                        .map(|html| (code, sanitize(&html))),
+                );
+            };
+            // the source as rendered is highlighted right away once hydrated
+            if first_run.is_none() {
+                highlight();
+                return;
+            }
+            pending
+                .set_value(set_timeout_with_handle(highlight, DEBOUNCE).ok());
+        });
+    }
+    let on_input = move |ev| source.set(event_target_value(&ev));
+    // keep the highlighted code scrolled along with the textarea
+    let on_scroll = move |_| {
+        if let (Some(textarea), Some(pre)) = (textarea.get(), pre.get()) {
+            pre.set_scroll_top(textarea.scroll_top());
+            pre.set_scroll_left(textarea.scroll_left());
+        }
+    };
+    let html = move || {
+        source.with(|source| {
+            highlighted.with(|highlighted| {
+                let highlighted = highlighted
+                    .as_ref()
+                    .map(|(code, html)| (code.as_str(), html.as_str()));
+                editor_markup(highlighted, source)
+            })
+        })
+    };
+    view! {
+        <div class="code-editor">
+            <pre aria-hidden="true" node_ref=pre>
+                <code class="hljs" inner_html=html></code>
+            </pre>
+            <textarea
+                node_ref=textarea
+                spellcheck="false"
+                autocapitalize="off"
+                readonly=move || !hydrated.get()
+                on:input=on_input
+                on:scroll=on_scroll
+            >
+                {source.get_untracked()}
+            </textarea>
+        </div>
+    }
+}
+
+#[component]
+fn CompareDemo() -> impl IntoView {
+    let query = leptos_router::hooks::use_query_map();
+    let slug = Memo::new(move |_| {
+        query
+            .with(|query| query.get("example"))
+            .and_then(|slug| {
+                EXAMPLES
+                    .iter()
+                    .find(|example| example.slug == slug)
+                    .map(|example| example.slug)
+            })
+            .unwrap_or(EXAMPLES[0].slug)
+    });
+    let reports = RwSignal::new(Vec::<FrameReport>::new());
+    // the reports are for the frames of the previous example until reloaded
+    Effect::new(move |_| {
+        slug.track();
+        reports.set(Vec::new());
+    });
+    #[cfg(not(feature = "ssr"))]
+    {
+        use gloo_utils::format::JsValueSerdeExt;
+
+        let handle = window_event_listener(leptos::ev::message, move |ev| {
+            if window().location().origin().ok() != Some(ev.origin()) {
+                return;
+            }
+            let Ok(report) = ev.data().into_serde::<FrameReport>() else {
+                return;
+            };
+            reports.update(|reports| {
+                reports.retain(|r| r.frame != report.frame);
+                reports.push(report);
+            });
+        });
+        on_cleanup(move || handle.remove());
+    }
+
+    let examples = EXAMPLES
+        .iter()
+        .filter(|example| example.slug != COMPARE_SLUG)
+        .map(|example| {
+            view! {
+                " "
+                <A href=format!("?example={}", example.slug)>{example.slug}</A>
+            }
+        })
+        .collect_view();
+    let frames = Frame::ALL
+        .into_iter()
+        .map(|frame| {
+            let status = move || {
+                reports.with(|reports| {
+                    reports
+                        .iter()
+                        .find(|report| report.frame == frame.name())
+                        .map_or(
+                            "waiting for the frame...".to_string(),
+                            |report| report.summary(),
+                        )
+                })
+            };
+            view! {
+                <figure>
+                    <figcaption>
+                        <strong>{frame.label()}</strong>
+                        <small>{status}</small>
+                    </figcaption>
+                    <iframe src=move || frame.src(slug.get())></iframe>
+                </figure>
+            }
+        })
+        .collect_view();
+    view! {
+        <h2>"Comparing the renderings of an example"</h2>
+        <p>"
+            The same example is shown as loaded freshly through SSR, as navigated to on the client after the
+            home page is hydrated, and as the HTML from the server without the WASM bundle (i.e. as if
+            hydration never happened).  Each frame reports whether it is hydrated and how many of its code
+            blocks are highlighted, so that the differences are visible at a glance.
+        "</p>
+        <p>"Example:"{examples}</p>
+        <div class="compare-frames">{frames}</div>
+    }
+}
+
+#[component]
+fn MarkdownDemo() -> impl IntoView {
+    view! { <MarkdownDoc id="markdown_demo.md"/> }
+}
+
+/// Render the Markdown `text` as views, with the fenced code blocks rendered
+/// by `CodeInner` as the language of the fence.
+#[component]
+pub fn Markdown(#[prop(into)] text: String) -> impl IntoView {
+    // highlight the code blocks only once hydrated, so that the markup is
+    // the same as rendered under SSR
+    provide_context(InnerEffect);
+    markdown_views(crate::markdown::parse(&text))
+}
+
+/// Render the Markdown fetched by `id` (see `fetch_code`).
+#[component]
+pub fn MarkdownDoc(#[prop(into)] id: String) -> impl IntoView {
+    let doc = Resource::new(
+        move || id.clone(),
+        |id| fetch_code_batched(id, Some("markdown".to_string())),
+    );
+    let doc_view = move || {
+        Suspend::new(async move {
+            doc.await.map(|doc| view! { <Markdown text=doc.source/> })
+        })
+    };
+    view! {
+        <Suspense fallback=move || view! { <p>"Loading..."</p> }>
+            {doc_view}
+        </Suspense>
+    }
+}
+
+fn markdown_views(nodes: Vec<Node>) -> AnyView {
+    nodes
+        .into_iter()
+        .map(markdown_view)
+        .collect_view()
+        .into_any()
+}
+
+fn markdown_view(node: Node) -> AnyView {
+    let (kind, children) = match node {
+        Node::Text(text) => return text.into_any(),
+        Node::Code(code) => return view! { <code>{code}</code> }.into_any(),
+        Node::SoftBreak => return "\n".into_any(),
+        Node::HardBreak => return view! { <br/> }.into_any(),
+        Node::Rule => return view! { <hr/> }.into_any(),
+        Node::CodeBlock { lang, code } => {
+            let lang = lang.unwrap_or_else(|| "plaintext".to_string());
+            return view! { <CodeInner code lang/> }.into_any();
+        }
+        Node::Element { kind, children } => (kind, children),
+    };
+    match kind {
+        Element::Image { src, title } => {
+            let alt = plain_text(&children);
+            let title = (!title.is_empty()).then_some(title);
+            match src {
+                Some(src) => {
+                    view! { <img src=src alt=alt title=title/> }.into_any()
+                }
+                None => alt.into_any(),
+            }
+        }
+        // the browser would add the `<tbody>` if it were missing, which
+        // would then fail hydration
+        Element::Table => {
+            let (head, body): (Vec<_>, Vec<_>) =
+                children.into_iter().partition(|node| {
+                    matches!(
+                        node,
+                        Node::Element {
+                            kind: Element::TableHead,
+                            ..
+                        }
+                    )
+                });
+            let head = markdown_views(head);
+            let body = markdown_views(body);
+            view! { <table>{head}<tbody>{body}</tbody></table> }.into_any()
+        }
+        kind => {
+            let children = markdown_views(children);
+            match kind {
+                Element::Paragraph => view! { <p>{children}</p> }.into_any(),
+                Element::Heading(1) => view! { <h1>{children}</h1> }.into_any(),
+                Element::Heading(2) => view! { <h2>{children}</h2> }.into_any(),
+                Element::Heading(3) => view! { <h3>{children}</h3> }.into_any(),
+                Element::Heading(4) => view! { <h4>{children}</h4> }.into_any(),
+                Element::Heading(5) => view! { <h5>{children}</h5> }.into_any(),
+                Element::Heading(_) => view! { <h6>{children}</h6> }.into_any(),
+                Element::BlockQuote => {
+                    view! { <blockquote>{children}</blockquote> }.into_any()
+                }
+                Element::List(Some(start)) => {
+                    view! { <ol start=start>{children}</ol> }.into_any()
+                }
+                Element::List(None) => view! { <ul>{children}</ul> }.into_any(),
+                Element::Item => view! { <li>{children}</li> }.into_any(),
+                Element::Emphasis => view! { <em>{children}</em> }.into_any(),
+                Element::Strong => {
+                    view! { <strong>{children}</strong> }.into_any()
+                }
+                Element::Strikethrough => {
+                    view! { <del>{children}</del> }.into_any()
+                }
+                Element::Link { href, title } => {
+                    let title = (!title.is_empty()).then_some(title);
+                    view! { <a href=href title=title>{children}</a> }.into_any()
+                }
+                Element::TableHead => {
+                    view! { <thead><tr>{children}</tr></thead> }.into_any()
+                }
+                Element::TableRow => view! { <tr>{children}</tr> }.into_any(),
+                Element::TableCell { head: true } => {
+                    view! { <th>{children}</th> }.into_any()
+                }
+                Element::TableCell { head: false } => {
+                    view! { <td>{children}</td> }.into_any()
+                }
+                Element::Image { .. } | Element::Table => unreachable!(),
+            }
+        }
+    }
+}
+
+#[component]
+fn RemoteHighlightDemo() -> impl IntoView {
+    provide_context(RemoteHighlight);
+    view! {
+        <h2>"Highlighting on the server"</h2>
+        <p>"
+            For clients where loading highlight.js is too heavy, the code may instead be highlighted by the
+            "<code>"highlight_code"</code>" server function, which produces the same markup (and so works
+            with the same themes) using a highlighter written in Rust.  The resulting markup is provided
+            through a resource, so it is identical between SSR and CSR and renders highlighted even with
+            JavaScript disabled.
+        "</p>
+        <CodeDemoWasmInner/>
+    }
+}
+
+#[component]
+fn StreamingCode() -> impl IntoView {
+    let query = leptos_router::hooks::use_query_map();
+    let id = query
+        .with_untracked(|q| q.get("id"))
+        .unwrap_or_else(|| CODE_ID.to_string());
+    view! {
+        <h2>"Streaming code from a server function"</h2>
+        <p>"
+            Rather than waiting for the entirety of a large file to arrive, the code may be provided by a
+            streaming server function in chunks of lines.  The first chunk is rendered through out-of-order
+            streaming under SSR so it is shown as soon as possible, with the remaining chunks streamed and
+            rendered by the client once hydrated.  Some other code may be selected through the "<code>"id"
+            </code>" query parameter (e.g. "<code>"?id=src/app.rs"</code>" when serving from a directory).
+        "</p>
+        <StreamedCode id lang="rust"/>
+    }
+}
+
+/// Render the code selected by `id` (as per `fetch_code`) as it streams in
+/// from `stream_code`, with each chunk highlighted once it is received.
+///
+/// The first chunk is loaded through a resource such that it is part of the
+/// SSR output.  When the client loaded it, the rest of the same stream is
+/// carried on with; after hydration, only the lines following the ones
+/// rendered by the server are requested.  As the chunks are highlighted
+/// separately, constructs spanning across chunks (e.g. block comments) may
+/// not be highlighted correctly.
+#[component]
+pub fn StreamedCode(id: String, #[prop(into)] lang: String) -> impl IntoView {
+    // highlight the chunks via effects to keep hydration consistent with SSR
+    provide_context(InnerEffect);
+    // the rest of the stream the first chunk was taken from by the client
+    #[cfg(not(feature = "ssr"))]
+    let remaining = StoredValue::new_local(
+        None::<
+            futures::stream::BoxStream<'static, Result<String, ServerFnError>>,
+        >,
+    );
+    let first = Resource::new(
+        {
+            let (id, lang) = (id.clone(), lang.clone());
+            move || (id.clone(), lang.clone())
+        },
+        move |(id, lang)| async move {
+            let stream = stream_code(id, Some(lang), 0).await?;
+            let mut chunks = line_chunks(stream.into_inner()).boxed();
+            let first =
+                chunks.next().await.unwrap_or_else(|| Ok(String::new()));
+            #[cfg(not(feature = "ssr"))]
+            remaining.set_value(Some(chunks));
+            first
+        },
+    );
+    let rest = RwSignal::new(Vec::<String>::new());
+    let error = RwSignal::new(None::<String>);
+    #[cfg(not(feature = "ssr"))]
+    Effect::new({
+        let (id, lang) = (id.clone(), lang.clone());
+        move |_| {
+            let Some(Ok(chunk)) = first.get() else {
+                return;
+            };
+            let stream = remaining.try_update_value(Option::take).flatten();
+            let skip = chunk.split_inclusive('\n').count();
+            let (id, lang) = (id.clone(), lang.clone());
+            rest.set(Vec::new());
+            leptos::task::spawn_local(async move {
+                let mut chunks = match stream {
+                    Some(chunks) => chunks,
+                    // the first chunk was rendered by the server
+                    None => match stream_code(id, Some(lang), skip).await {
+                        Ok(stream) => line_chunks(stream.into_inner()).boxed(),
+                        Err(err) => return error.set(Some(err.to_string())),
+                    },
+                };
+                while let Some(chunk) = chunks.next().await {
+                    match chunk {
+                        Ok(chunk) => rest.update(|rest| rest.push(chunk)),
+                        Err(err) => return error.set(Some(err.to_string())),
+                    }
+                }
+            });
+        }
+    });
+    // only the chunk rendered by the server needs a copy button that works
+    // without the client
+    let first_view = {
+        let lang = lang.clone();
+        move || {
+            let (id, lang) = (id.clone(), lang.clone());
+            Suspend::new(async move {
+                first.await.map(|chunk| {
+                    view! { <CodeInner code=chunk lang source_id=id/> }
+                })
+            })
+        }
+    };
+    view! {
+        <div class="streamed-code">
+            <Suspense fallback=move || view! { <p>"Loading code..."</p> }>
+                {first_view}
+            </Suspense>
+            <For
+                each=move || rest.get().into_iter().enumerate()
+                key=|(n, _)| *n
+                children=move |(_, chunk)| {
+                    view! { <CodeInner code=chunk lang=lang.clone()/> }
+                }
+            />
+            {move || error.get().map(|err| view! { <p class="error">{err}</p> })}
+        </div>
+    }
+}
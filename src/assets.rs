@@ -0,0 +1,642 @@
+// Serving of the vendored `@highlightjs/cdn-assets` tree (the standard and
+// ES module builds, plus the themes under `styles/`) through Axum, so that
+// they may be served either in the cache busting manner the demos rely on
+// or with the usual validators for production use.  The gzip and brotli
+// encoded versions of these are produced by `build.rs` and are served when
+// the client accepts them.
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    path::{Component, PathBuf},
+    time::SystemTime,
+};
+
+pub const HLJS_ROOT: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/node_modules/@highlightjs/cdn-assets"
+);
+pub const HLJS_PRECOMPRESSED: &str = concat!(env!("OUT_DIR"), "/precompressed");
+
+/// The content encodings that assets may be precompressed with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    /// The extension of the precompressed file (without the leading `.`).
+    pub fn extension(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gz",
+        }
+    }
+
+    pub fn header_value(self) -> HeaderValue {
+        match self {
+            Encoding::Brotli => HeaderValue::from_static("br"),
+            Encoding::Gzip => HeaderValue::from_static("gzip"),
+        }
+    }
+
+    /// The encoding of a response, going by its `Content-Encoding`.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        match headers.get(header::CONTENT_ENCODING)?.to_str().ok()? {
+            "br" => Some(Encoding::Brotli),
+            "gzip" => Some(Encoding::Gzip),
+            _ => None,
+        }
+    }
+
+    /// The preferred encoding out of the ones listed in the request's
+    /// `Accept-Encoding`; brotli wins over gzip when both are acceptable.
+    /// The `*` wildcard stands for any coding not otherwise listed, so the
+    /// codings refused with `q=0` are never selected through it.
+    pub fn negotiate(headers: &HeaderMap) -> Option<Self> {
+        let codings = headers
+            .get_all(header::ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|item| {
+                let mut parts = item.split(';').map(str::trim);
+                let coding = parts.next().filter(|c| !c.is_empty())?;
+                let q = parts
+                    .find_map(|p| p.strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+                Some((coding.to_ascii_lowercase(), q > 0.0))
+            })
+            .collect::<Vec<_>>();
+        let listed = |coding: &str| {
+            codings
+                .iter()
+                .find(|(c, _)| c == coding)
+                .map(|(_, accepted)| *accepted)
+        };
+        let wildcard = listed("*").unwrap_or(false);
+        [Encoding::Brotli, Encoding::Gzip]
+            .into_iter()
+            .find(|encoding| {
+                let coding = encoding.header_value();
+                listed(coding.to_str().expect("coding is ascii"))
+                    .unwrap_or(wildcard)
+            })
+    }
+
+    /// Encode the bytes, using the best compression unless `fast` is set,
+    /// which is meant for responses that have to be encoded on every
+    /// request.
+    pub fn encode(self, bytes: &[u8], fast: bool) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                let quality = if fast { 4 } else { 11 };
+                let mut writer = brotli::CompressorWriter::new(
+                    Vec::new(),
+                    4096,
+                    quality,
+                    22,
+                );
+                writer.write_all(bytes)?;
+                Ok(writer.into_inner())
+            }
+            Encoding::Gzip => {
+                let level = if fast {
+                    flate2::Compression::fast()
+                } else {
+                    flate2::Compression::best()
+                };
+                let mut writer =
+                    flate2::write::GzEncoder::new(Vec::new(), level);
+                writer.write_all(bytes)?;
+                writer.finish()
+            }
+        }
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut result = Vec::new();
+        match self {
+            Encoding::Brotli => {
+                brotli::Decompressor::new(bytes, 4096)
+                    .read_to_end(&mut result)?;
+            }
+            Encoding::Gzip => {
+                flate2::read::GzDecoder::new(bytes).read_to_end(&mut result)?;
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// How responses from an asset route may be cached by the browser.
+///
+/// This is inserted into the extensions of every response produced by the
+/// asset route, so that later middleware (e.g. the one that injects latency)
+/// can tell whether they are allowed to tamper with the response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Provide no validators and forbid storing the response, such that
+    /// every navigation fetches the script again as the demos require.
+    Bust,
+    /// Provide `ETag` and `Last-Modified`, answer conditional requests with
+    /// `304 Not Modified`, and mark requests carrying the matching content
+    /// hash (i.e. `?v=<hash>`) as immutable.
+    Immutable,
+}
+
+#[derive(Clone)]
+struct Assets {
+    root: PathBuf,
+    precompressed: PathBuf,
+    mode: CacheMode,
+}
+
+/// A router serving the files under `root` using the provided `mode`,
+/// intended to be nested under some prefix of the application router.  The
+/// encoded versions of the files are looked up under `precompressed` with
+/// the same relative path plus the extension of the encoding.
+pub fn router<S>(
+    root: impl Into<PathBuf>,
+    precompressed: impl Into<PathBuf>,
+    mode: CacheMode,
+) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/*path", get(serve))
+        .with_state(Assets {
+            root: root.into(),
+            precompressed: precompressed.into(),
+            mode,
+        })
+}
+
+/// Write the gzip and brotli encoded versions of the scripts, stylesheets
+/// and WASM files under `dir` next to them, skipping the ones that are
+/// already up to date.  This is meant for the output of `cargo leptos
+/// build` which is not available to `build.rs`, such that it may then be
+/// served by `ServeDir` with its `precompressed_*` options.
+pub fn precompress_dir(dir: &std::path::Path) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            precompress_dir(&path)?;
+            continue;
+        }
+        if !matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("js" | "css" | "wasm")
+        ) {
+            continue;
+        }
+        let modified = path.metadata()?.modified()?;
+        for encoding in [Encoding::Brotli, Encoding::Gzip] {
+            let mut target = path.clone().into_os_string();
+            target.push(".");
+            target.push(encoding.extension());
+            let target = PathBuf::from(target);
+            if target
+                .metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|t| t >= modified)
+            {
+                continue;
+            }
+            std::fs::write(
+                target,
+                encoding.encode(&std::fs::read(&path)?, false)?,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// The URL of the ES module build of highlight.js within the bundle, where
+/// wasm-bindgen places it as a snippet (imported by the `hljs` bindings).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HljsModuleUrl(pub String);
+
+/// Locate the ES module build of highlight.js among the snippets of the
+/// bundle under `pkg_dir`, which is served under `prefix`.  The directory of
+/// the snippets is named after a hash of the crate, so it has to be looked
+/// up.
+pub fn hljs_module_url(
+    pkg_dir: &std::path::Path,
+    prefix: &str,
+) -> Option<HljsModuleUrl> {
+    const MODULE: &str =
+        "node_modules/@highlightjs/cdn-assets/es/highlight.min.js";
+    std::fs::read_dir(pkg_dir.join("snippets"))
+        .ok()?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .find(|name| pkg_dir.join("snippets").join(name).join(MODULE).is_file())
+        .map(|name| HljsModuleUrl(format!("{prefix}/snippets/{name}/{MODULE}")))
+}
+
+/// The hash used for the `ETag` of the content, which is also the value
+/// expected for the `v` query parameter for immutable caching.
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)[..16]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Produce the URL for the asset at `path` under the `prefix` it was nested
+/// under, with the content hash appended so it may be cached indefinitely.
+pub fn versioned_url(
+    root: impl Into<PathBuf>,
+    prefix: &str,
+    path: &str,
+) -> std::io::Result<String> {
+    let bytes = std::fs::read(root.into().join(path))?;
+    Ok(format!("{prefix}/{path}?v={}", content_hash(&bytes)))
+}
+
+async fn serve(
+    State(assets): State<Assets>,
+    Path(path): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    let relative = std::path::Path::new(&path);
+    if !relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let target = assets.root.join(relative);
+    let (bytes, modified) = match tokio::fs::read(&target).await {
+        Ok(bytes) => {
+            let modified = tokio::fs::metadata(&target)
+                .await
+                .and_then(|m| m.modified())
+                .ok();
+            (bytes, modified)
+        }
+        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let content_type = mime_guess::from_path(&target)
+        .first_raw()
+        .unwrap_or("application/octet-stream");
+    // The hash is always derived from the identity encoding, as that is what
+    // `versioned_url` provides.
+    let hash = content_hash(&bytes);
+
+    let (mut encoding, mut bytes) = (None, bytes);
+    if let Some(accepted) = Encoding::negotiate(&headers) {
+        let path = assets
+            .precompressed
+            .join(format!("{path}.{}", accepted.extension()));
+        if let Ok(encoded) = tokio::fs::read(path).await {
+            encoding = Some(accepted);
+            bytes = encoded;
+        }
+    }
+
+    let mut res = match assets.mode {
+        CacheMode::Bust => (
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::CACHE_CONTROL, "no-store"),
+            ],
+            bytes,
+        )
+            .into_response(),
+        CacheMode::Immutable => {
+            // Each representation requires its own entity tag.
+            let etag = match encoding {
+                Some(encoding) => {
+                    format!("\"{hash}-{}\"", encoding.extension())
+                }
+                None => format!("\"{hash}\""),
+            };
+            let cache_control = if query.get("v") == Some(&hash) {
+                "public, max-age=31536000, immutable"
+            } else {
+                "no-cache"
+            };
+            let mut res = if is_fresh(&headers, &etag, modified) {
+                StatusCode::NOT_MODIFIED.into_response()
+            } else {
+                let mut res = Response::new(Body::from(bytes));
+                res.headers_mut().insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(content_type),
+                );
+                res
+            };
+            let headers = res.headers_mut();
+            headers.insert(
+                header::CACHE_CONTROL,
+                HeaderValue::from_static(cache_control),
+            );
+            headers.insert(
+                header::ETAG,
+                HeaderValue::from_str(&etag).expect("hex digest is ascii"),
+            );
+            if let Some(modified) = modified {
+                headers.insert(
+                    header::LAST_MODIFIED,
+                    HeaderValue::from_str(&httpdate::fmt_http_date(modified))
+                        .expect("http date is ascii"),
+                );
+            }
+            res
+        }
+    };
+    let headers = res.headers_mut();
+    headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    if let Some(encoding) = encoding {
+        headers.insert(header::CONTENT_ENCODING, encoding.header_value());
+    }
+    res.extensions_mut().insert(assets.mode);
+    res
+}
+
+// As per RFC 9110, `If-None-Match` takes precedence over `If-Modified-Since`
+// when both are present.
+fn is_fresh(
+    headers: &HeaderMap,
+    etag: &str,
+    modified: Option<SystemTime>,
+) -> bool {
+    if let Some(value) = headers.get(header::IF_NONE_MATCH) {
+        return value.to_str().is_ok_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+        });
+    }
+    match (
+        headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| httpdate::parse_http_date(v).ok()),
+        modified,
+    ) {
+        // http dates only have a resolution of a second
+        (Some(since), Some(modified)) => modified
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .ok()
+            .zip(since.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .is_some_and(|(modified, since)| {
+                modified.as_secs() <= since.as_secs()
+            }),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    const SCRIPT: &str = "var hljs = {};";
+
+    fn assets(mode: CacheMode) -> (tempfile::TempDir, Router) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("hl.js"), SCRIPT).unwrap();
+        let app = Router::new().nest(
+            "/hljs",
+            router(dir.path(), dir.path().join("precompressed"), mode),
+        );
+        (dir, app)
+    }
+
+    async fn get(
+        app: &Router,
+        uri: &str,
+        headers: &[(header::HeaderName, &str)],
+    ) -> Response {
+        let mut req = Request::get(uri);
+        for (name, value) in headers {
+            req = req.header(name, *value);
+        }
+        app.clone()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn immutable_provides_validators() {
+        let (_dir, app) = assets(CacheMode::Immutable);
+        let res = get(&app, "/hljs/hl.js", &[]).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let headers = res.headers().clone();
+        assert_eq!(
+            headers[header::ETAG],
+            format!("\"{}\"", content_hash(SCRIPT.as_bytes()))
+        );
+        assert!(headers.contains_key(header::LAST_MODIFIED));
+        assert_eq!(headers[header::CACHE_CONTROL], "no-cache");
+        assert_eq!(res.extensions().get(), Some(&CacheMode::Immutable));
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, SCRIPT);
+    }
+
+    #[tokio::test]
+    async fn immutable_answers_conditional_requests() {
+        let (_dir, app) = assets(CacheMode::Immutable);
+        let res = get(&app, "/hljs/hl.js", &[]).await;
+        let etag = res.headers()[header::ETAG].to_str().unwrap().to_owned();
+        let modified = res.headers()[header::LAST_MODIFIED]
+            .to_str()
+            .unwrap()
+            .to_owned();
+
+        let res =
+            get(&app, "/hljs/hl.js", &[(header::IF_NONE_MATCH, &etag)]).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers()[header::ETAG], etag.as_str());
+        let res =
+            get(&app, "/hljs/hl.js", &[(header::IF_NONE_MATCH, "\"stale\"")])
+                .await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = get(
+            &app,
+            "/hljs/hl.js",
+            &[(header::IF_MODIFIED_SINCE, &modified)],
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        let res = get(
+            &app,
+            "/hljs/hl.js",
+            &[(header::IF_MODIFIED_SINCE, "Thu, 01 Jan 1970 00:00:00 GMT")],
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        // If-None-Match takes precedence over If-Modified-Since
+        let res = get(
+            &app,
+            "/hljs/hl.js",
+            &[
+                (header::IF_NONE_MATCH, "\"stale\""),
+                (header::IF_MODIFIED_SINCE, &modified),
+            ],
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn immutable_only_for_matching_version() {
+        let (_dir, app) = assets(CacheMode::Immutable);
+        let hash = content_hash(SCRIPT.as_bytes());
+        let res = get(&app, &format!("/hljs/hl.js?v={hash}"), &[]).await;
+        assert_eq!(
+            res.headers()[header::CACHE_CONTROL],
+            "public, max-age=31536000, immutable"
+        );
+        let res = get(&app, "/hljs/hl.js?v=0123456789abcdef", &[]).await;
+        assert_eq!(res.headers()[header::CACHE_CONTROL], "no-cache");
+    }
+
+    #[tokio::test]
+    async fn bust_forbids_caching() {
+        let (dir, app) = assets(CacheMode::Bust);
+        let url =
+            versioned_url(dir.path(), "/hljs", "hl.js").expect("file exists");
+        for uri in ["/hljs/hl.js", url.as_str()] {
+            let res = get(&app, uri, &[]).await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.headers()[header::CACHE_CONTROL], "no-store");
+            assert!(!res.headers().contains_key(header::ETAG));
+            assert!(!res.headers().contains_key(header::LAST_MODIFIED));
+            assert_eq!(res.extensions().get(), Some(&CacheMode::Bust));
+        }
+        // conditional requests are never answered with a 304
+        let res =
+            get(&app, "/hljs/hl.js", &[(header::IF_NONE_MATCH, "*")]).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn negotiation() {
+        let negotiate = |accept: &str| {
+            Encoding::negotiate(&HeaderMap::from_iter([(
+                header::ACCEPT_ENCODING,
+                HeaderValue::from_str(accept).unwrap(),
+            )]))
+        };
+        assert_eq!(Encoding::negotiate(&HeaderMap::new()), None);
+        for (accept, expected) in [
+            ("", None),
+            ("identity", None),
+            ("gzip", Some(Encoding::Gzip)),
+            ("gzip, deflate, br", Some(Encoding::Brotli)),
+            ("BR", Some(Encoding::Brotli)),
+            ("br;q=0, gzip", Some(Encoding::Gzip)),
+            ("br;q=0, gzip;q=0", None),
+            ("*", Some(Encoding::Brotli)),
+            ("br;q=0, *", Some(Encoding::Gzip)),
+            ("*, br;q=0", Some(Encoding::Gzip)),
+            ("br;q=0, gzip;q=0, *", None),
+            ("*;q=0", None),
+            ("*;q=0, gzip", Some(Encoding::Gzip)),
+            ("gzip;q=bogus", None),
+        ] {
+            assert_eq!(negotiate(accept), expected, "{accept:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_precompressed() {
+        let (dir, app) = assets(CacheMode::Immutable);
+        let precompressed = dir.path().join("precompressed");
+        std::fs::create_dir(&precompressed).unwrap();
+        let encoded = Encoding::Gzip.encode(SCRIPT.as_bytes(), false).unwrap();
+        std::fs::write(precompressed.join("hl.js.gz"), &encoded).unwrap();
+
+        let res = get(&app, "/hljs/hl.js", &[]).await;
+        assert_eq!(res.headers()[header::VARY], "accept-encoding");
+        assert!(!res.headers().contains_key(header::CONTENT_ENCODING));
+
+        let res =
+            get(&app, "/hljs/hl.js", &[(header::ACCEPT_ENCODING, "gzip")])
+                .await;
+        assert_eq!(res.headers()[header::VARY], "accept-encoding");
+        assert_eq!(res.headers()[header::CONTENT_ENCODING], "gzip");
+        let etag = res.headers()[header::ETAG].to_str().unwrap().to_owned();
+        assert_eq!(etag, format!("\"{}-gz\"", content_hash(SCRIPT.as_bytes())));
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, encoded);
+
+        // brotli is preferred, but falls back to identity when missing
+        let res =
+            get(&app, "/hljs/hl.js", &[(header::ACCEPT_ENCODING, "br")]).await;
+        assert_eq!(res.headers()[header::VARY], "accept-encoding");
+        assert!(!res.headers().contains_key(header::CONTENT_ENCODING));
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, SCRIPT);
+    }
+
+    #[test]
+    fn precompresses_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("nested")).unwrap();
+        let script = dir.path().join("nested/app.js");
+        std::fs::write(&script, SCRIPT).unwrap();
+        std::fs::write(dir.path().join("index.html"), "<html>").unwrap();
+        precompress_dir(dir.path()).unwrap();
+
+        for encoding in [Encoding::Brotli, Encoding::Gzip] {
+            let encoded = std::fs::read(
+                dir.path()
+                    .join(format!("nested/app.js.{}", encoding.extension())),
+            )
+            .unwrap();
+            assert_eq!(encoding.decode(&encoded).unwrap(), SCRIPT.as_bytes());
+        }
+        assert!(!dir.path().join("index.html.gz").exists());
+    }
+
+    #[test]
+    fn locates_hljs_module() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(hljs_module_url(dir.path(), "/pkg"), None);
+        let snippet = dir.path().join(
+            "snippets/axum_js_ssr-0123abcd/\
+             node_modules/@highlightjs/cdn-assets/es",
+        );
+        std::fs::create_dir_all(&snippet).unwrap();
+        std::fs::create_dir(dir.path().join("snippets/other")).unwrap();
+        assert_eq!(hljs_module_url(dir.path(), "/pkg"), None);
+        std::fs::write(snippet.join("highlight.min.js"), SCRIPT).unwrap();
+        assert_eq!(
+            hljs_module_url(dir.path(), "/pkg"),
+            Some(HljsModuleUrl(
+                "/pkg/snippets/axum_js_ssr-0123abcd/\
+                 node_modules/@highlightjs/cdn-assets/es/highlight.min.js"
+                    .to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_paths_outside_root() {
+        let (_dir, app) = assets(CacheMode::Immutable);
+        let res = get(&app, "/hljs/../hl.js", &[]).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = get(&app, "/hljs/missing.js", &[]).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,187 @@
+// Coalescing of the `fetch_code` requests made by the client, such that all
+// the requests issued within the same tick (e.g. by the resources of every
+// code block on a page) are made through a single `fetch_codes` round trip.
+// Under SSR the server function is simply called directly, as there is no
+// round trip to be saved.
+use crate::api::{Code, CodeError};
+use leptos::prelude::ServerFnError;
+
+#[cfg(not(feature = "ssr"))]
+mod csr {
+    use super::*;
+    use crate::api::fetch_codes;
+    use futures::channel::oneshot;
+    use std::cell::RefCell;
+
+    type Reply = oneshot::Sender<Result<Code, ServerFnError<CodeError>>>;
+
+    thread_local! {
+        static PENDING: RefCell<Vec<(String, Option<String>, Reply)>> =
+            const { RefCell::new(Vec::new()) };
+    }
+
+    /// Fetch the code as `fetch_code` would, but batched together with the
+    /// other requests made in the same tick.
+    pub fn fetch_code_batched(
+        id: String,
+        lang: Option<String>,
+    ) -> impl std::future::Future<Output = Result<Code, ServerFnError<CodeError>>>
+           + Send
+           + 'static {
+        let (tx, rx) = oneshot::channel();
+        let first = PENDING.with_borrow_mut(|pending| {
+            pending.push((id, lang, tx));
+            pending.len() == 1
+        });
+        // the first request of the tick schedules the flush for the batch
+        if first {
+            leptos::prelude::queue_microtask(flush);
+        }
+        async move {
+            rx.await.unwrap_or_else(|_| {
+                Err(ServerFnError::ServerError(
+                    "missing from the batched response".to_string(),
+                ))
+            })
+        }
+    }
+
+    fn flush() {
+        let pending = PENDING.with_borrow_mut(std::mem::take);
+        for (ids, lang, replies) in batches(pending) {
+            leptos::task::spawn_local(async move {
+                match fetch_codes(ids, lang).await {
+                    Ok(results) => {
+                        for (tx, result) in replies.into_iter().zip(results) {
+                            let _ = tx
+                                .send(result.map_err(
+                                    ServerFnError::WrappedServerError,
+                                ));
+                        }
+                    }
+                    Err(err) => {
+                        for tx in replies {
+                            let _ = tx.send(Err(err.clone()));
+                        }
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// Group the `pending` requests into the `fetch_codes` calls to be made, as
+/// the `ids`, `lang` and replies of each.  As `fetch_codes` takes a single
+/// `lang` there is a call for each, in the order first requested, which is
+/// further split such that no call exceeds `FETCH_CODES_MAX_IDS`.
+#[cfg(any(not(feature = "ssr"), test))]
+fn batches<R>(
+    pending: Vec<(String, Option<String>, R)>,
+) -> Vec<(Vec<String>, Option<String>, Vec<R>)> {
+    use crate::api::FETCH_CODES_MAX_IDS;
+
+    let mut batches = Vec::<(Vec<String>, Option<String>, Vec<R>)>::new();
+    for (id, lang, reply) in pending {
+        match batches.iter_mut().rev().find(|(ids, batch_lang, _)| {
+            *batch_lang == lang && ids.len() < FETCH_CODES_MAX_IDS
+        }) {
+            Some((ids, _, replies)) => {
+                ids.push(id);
+                replies.push(reply);
+            }
+            None => batches.push((vec![id], lang, vec![reply])),
+        }
+    }
+    batches
+}
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use super::*;
+    use crate::api::fetch_code;
+
+    pub async fn fetch_code_batched(
+        id: String,
+        lang: Option<String>,
+    ) -> Result<Code, ServerFnError<CodeError>> {
+        fetch_code(id, lang).await
+    }
+}
+
+#[cfg(not(feature = "ssr"))]
+pub use csr::*;
+#[cfg(feature = "ssr")]
+pub use ssr::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::FETCH_CODES_MAX_IDS;
+
+    fn pending(
+        requests: &[(&str, Option<&str>)],
+    ) -> Vec<(String, Option<String>, usize)> {
+        requests
+            .iter()
+            .enumerate()
+            .map(|(n, (id, lang))| {
+                (id.to_string(), lang.map(str::to_string), n)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn batched_by_lang() {
+        let batches = batches(pending(&[
+            ("a", None),
+            ("b", Some("rust")),
+            ("c", None),
+            ("d", Some("rust")),
+            ("e", Some("toml")),
+        ]));
+        assert_eq!(
+            batches,
+            [
+                (vec!["a".into(), "c".into()], None, vec![0, 2]),
+                (
+                    vec!["b".into(), "d".into()],
+                    Some("rust".into()),
+                    vec![1, 3]
+                ),
+                (vec!["e".into()], Some("toml".into()), vec![4]),
+            ]
+        );
+    }
+
+    #[test]
+    fn batches_capped() {
+        let ids = (0..FETCH_CODES_MAX_IDS * 2 + 1)
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>();
+        let batches = batches(pending(
+            &ids.iter().map(|id| (id.as_str(), None)).collect::<Vec<_>>(),
+        ));
+        assert_eq!(
+            batches
+                .iter()
+                .map(|(ids, ..)| ids.len())
+                .collect::<Vec<_>>(),
+            [FETCH_CODES_MAX_IDS, FETCH_CODES_MAX_IDS, 1]
+        );
+        // every reply still matches up with its id, in the requested order
+        for (ids, _, replies) in &batches {
+            for (id, reply) in ids.iter().zip(replies) {
+                assert_eq!(*id, reply.to_string());
+            }
+        }
+        assert_eq!(
+            batches.iter().flat_map(|(ids, ..)| ids).collect::<Vec<_>>(),
+            ids.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn nothing_pending() {
+        assert!(batches(Vec::<(String, Option<String>, ())>::new()).is_empty());
+    }
+}
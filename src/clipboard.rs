@@ -0,0 +1,92 @@
+// Copying of code to the clipboard for the copy buttons of the code blocks.
+// Rather than the page carrying another copy of every listing for its
+// button, the code is read back from the rendered `<code>` when clicked.
+
+/// The text of the code from its rendered `lines`, without the empty lines
+/// it may have been padded with (see `lines::pad_lines`).
+pub fn lines_text<'a>(lines: impl IntoIterator<Item = &'a str>) -> String {
+    let mut text = lines.into_iter().collect::<Vec<_>>().join("\n");
+    text.truncate(text.trim_end_matches('\n').len());
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(not(feature = "ssr"))]
+mod csr {
+    use super::lines_text;
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    /// The text of the code shown along with the `button`, i.e. of the first
+    /// `<pre><code>` within the same parent element, or of its lines when
+    /// rendered line by line (such that the line numbers and diff markers
+    /// are left out).
+    pub fn code_text(button: &web_sys::Element) -> Option<String> {
+        let parent = button.parent_element()?;
+        let lines = parent.query_selector_all(".line-content").ok()?;
+        if lines.length() > 0 {
+            let lines = (0..lines.length())
+                .filter_map(|n| lines.item(n)?.text_content())
+                .collect::<Vec<_>>();
+            return Some(lines_text(lines.iter().map(String::as_str)));
+        }
+        let code = parent.query_selector("pre code").ok()??.text_content()?;
+        Some(lines_text(code.lines()))
+    }
+
+    /// Copy the text to the clipboard through the Clipboard API, falling
+    /// back to `document.execCommand("copy")` where the API is unavailable
+    /// (e.g. in insecure contexts) or fails, returning whether it worked.
+    pub async fn copy_text(text: &str) -> bool {
+        let clipboard = leptos::prelude::window().navigator().clipboard();
+        if !clipboard.is_undefined()
+            && JsFuture::from(clipboard.write_text(text)).await.is_ok()
+        {
+            return true;
+        }
+        exec_command_copy(text)
+    }
+
+    fn exec_command_copy(text: &str) -> bool {
+        let document = leptos::prelude::document();
+        let (Some(body), Ok(textarea)) =
+            (document.body(), document.create_element("textarea"))
+        else {
+            return false;
+        };
+        let textarea =
+            textarea.unchecked_into::<web_sys::HtmlTextAreaElement>();
+        textarea.set_value(text);
+        let _ = textarea.set_attribute("readonly", "");
+        let _ = textarea
+            .set_attribute("style", "position: fixed; top: 0; opacity: 0;");
+        if body.append_child(&textarea).is_err() {
+            return false;
+        }
+        textarea.select();
+        let copied = document
+            .unchecked_ref::<web_sys::HtmlDocument>()
+            .exec_command("copy")
+            .unwrap_or(false);
+        textarea.remove();
+        copied
+    }
+}
+
+#[cfg(not(feature = "ssr"))]
+pub use csr::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_from_lines() {
+        assert_eq!(lines_text(["fn main() {", "}"]), "fn main() {\n}\n");
+        // without the padding
+        assert_eq!(lines_text("a\n\nb\n\n\n".lines()), "a\n\nb\n");
+        assert_eq!(lines_text(["", ""]), "");
+    }
+}
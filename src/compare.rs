@@ -0,0 +1,156 @@
+// Support for the comparison of an example as rendered in different ways,
+// side by side in `<iframe>`s: freshly loaded through SSR, navigated to on
+// the client after hydration, and as the HTML from the server without the
+// WASM bundle.  The pages loaded in the frames are told which frame they are
+// through the `compare` query parameter, which has the shell include the
+// `FRAME_REPORTER_JS` that reports the hydration and highlighting status of
+// the frame to the comparison page through `postMessage`.
+use serde::{Deserialize, Serialize};
+
+/// The slug of the comparison page in the example registry, which is not
+/// itself an example to be compared.
+pub const COMPARE_SLUG: &str = "compare";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Frame {
+    /// The example loaded directly, rendered through SSR and then hydrated.
+    Ssr,
+    /// The home page loaded and hydrated, which then navigates to the
+    /// example on the client.
+    Csr,
+    /// The example rendered through SSR without the hydration scripts.
+    Html,
+}
+
+impl Frame {
+    pub const ALL: [Frame; 3] = [Frame::Ssr, Frame::Csr, Frame::Html];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Frame::Ssr => "ssr",
+            Frame::Csr => "csr",
+            Frame::Html => "html",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Frame::Ssr => "Fresh SSR load",
+            Frame::Csr => "Navigated to on the client",
+            Frame::Html => "Server HTML without WASM",
+        }
+    }
+
+    /// The URL for the frame to load for the example.
+    pub fn src(self, slug: &str) -> String {
+        match self {
+            Frame::Csr => format!("/?compare=csr&navigate={slug}"),
+            frame => format!("/{slug}?compare={}", frame.name()),
+        }
+    }
+
+    /// The frame the page is loaded in, as per the query string.
+    pub fn from_query(query: &str) -> Option<Self> {
+        let name = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("compare="))?;
+        Frame::ALL.into_iter().find(|frame| frame.name() == name)
+    }
+
+    /// Whether the page loaded in the frame should be hydrated.
+    pub fn hydrates(self) -> bool {
+        self != Frame::Html
+    }
+}
+
+/// The status of a frame, as reported by `FRAME_REPORTER_JS`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameReport {
+    pub frame: String,
+    pub path: String,
+    pub hydrated: bool,
+    pub panicked: bool,
+    /// The number of code blocks with highlighted markup.
+    pub highlighted: u32,
+    pub blocks: u32,
+}
+
+impl FrameReport {
+    pub fn summary(&self) -> String {
+        let status = if self.panicked {
+            "panicked"
+        } else if self.hydrated {
+            "hydrated"
+        } else {
+            "not hydrated"
+        };
+        format!(
+            "{status}, {} of {} code blocks highlighted, at {}",
+            self.highlighted, self.blocks, self.path
+        )
+    }
+}
+
+/// The script reporting the status of the page to the comparison page it
+/// is framed in, whenever the page changes.  It is also what navigates the
+/// client-side frame to the example (as named by the `navigate` query
+/// parameter) once hydrated, by clicking on its link in the navigation.
+pub const FRAME_REPORTER_JS: &str = r##"(() => {
+  const params = new URLSearchParams(location.search);
+  const frame = params.get("compare");
+  const navigate = params.get("navigate");
+  let queued = false;
+  const report = () => {
+    queued = false;
+    const blocks = [...document.querySelectorAll("pre code")];
+    parent.postMessage({
+      frame,
+      path: location.pathname,
+      hydrated: window._leptos_hydrated === true,
+      panicked: document.querySelector("#notice.panicked") !== null,
+      highlighted: blocks.filter(code => code.classList.contains("hljs")
+        || code.querySelector('[class^="hljs-"]')).length,
+      blocks: blocks.length,
+    }, location.origin);
+  };
+  const queue = () => {
+    if (!queued) {
+      queued = true;
+      requestAnimationFrame(report);
+    }
+  };
+  document.addEventListener("_leptos_hydrated", () => {
+    if (navigate) {
+      document.querySelector(`nav a[href="/${CSS.escape(navigate)}"]`)?.click();
+    }
+    queue();
+  });
+  document.addEventListener("DOMContentLoaded", () => {
+    new MutationObserver(queue).observe(document.body, {
+      subtree: true,
+      childList: true,
+      attributes: true,
+      characterData: true,
+    });
+    queue();
+  });
+})();"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_urls() {
+        assert_eq!(Frame::Ssr.src("naive"), "/naive?compare=ssr");
+        assert_eq!(Frame::Csr.src("naive"), "/?compare=csr&navigate=naive");
+        assert_eq!(Frame::Html.src("naive"), "/naive?compare=html");
+        for frame in Frame::ALL {
+            let src = frame.src("naive");
+            let query = src.split_once('?').unwrap().1;
+            assert_eq!(Frame::from_query(query), Some(frame));
+        }
+        assert_eq!(Frame::from_query("compare=bogus"), None);
+        assert_eq!(Frame::from_query("x=1"), None);
+    }
+}
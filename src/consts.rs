@@ -9,17 +9,14 @@ pub const CH03_05A: &str = r#"fn main() {
         println!("condition was false");
     }
 }
-
-
-
-
 "#;
 
 // For some reason, swapping the code examples "fixes" example 6.  It
 // might have something to do with the lower complexity of highlighting
-// a shorter example.  Anyway, including extra newlines for the shorter
-// example to match with the longer in order to avoid reflowing the
-// table during the async resource loading for CSR.
+// a shorter example.  Anyway, the shorter example is padded to the length
+// of the longer where they are shown side by side (see `lines::pad_lines`),
+// in order to avoid reflowing the table during the async resource loading
+// for CSR.
 
 pub const CH05_02A: &str = r#"fn main() {
     let width1 = 30;
@@ -36,4 +33,41 @@ fn area(width: u32, height: u32) -> u32 {
 }
 "#;
 
+pub const MARKDOWN_DEMO: &str = r#"## Rendered from Markdown
+
+This page is written in *Markdown*, fetched through `fetch_code` and then
+rendered by the `Markdown` component into views, where the fenced code blocks
+go through `CodeInner` with the language of the fence:
+
+```rust
+fn main() {
+    let number = 7;
+
+    if number < 5 {
+        println!("condition was true");
+    } else {
+        println!("condition was false");
+    }
+}
+```
+
+| Markdown          | Rendered as                  |
+|-------------------|------------------------------|
+| fenced code block | `CodeInner`                  |
+| raw HTML          | nothing, as it is dropped    |
+| other elements    | the corresponding elements   |
+
+> As the Markdown is parsed in the same way under SSR and CSR, the views are
+> identical for hydration.
+"#;
+
 pub const LEPTOS_HYDRATED: &str = "_leptos_hydrated";
+
+// The `sha384-...` integrity values of `highlight.min.js` and its ES module
+// counterpart, i.e. `HIGHLIGHT_MIN_JS_SRI` and `HIGHLIGHT_ES_MIN_JS_SRI`, as
+// generated by `build.rs` from the vendored copies.
+include!(concat!(env!("OUT_DIR"), "/sri.rs"));
+
+// The names of the themes shipped under `styles/` of the vendored
+// highlight.js (e.g. `github-dark` for `styles/github-dark.min.css`).
+include!(concat!(env!("OUT_DIR"), "/themes.rs"));
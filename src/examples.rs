@@ -0,0 +1,289 @@
+// The registry of the examples, from which both the navigation and the routes
+// are generated, such that adding an example is a matter of adding an entry
+// to `app::EXAMPLES`.  The numbering shown in the navigation is derived from
+// the order of the entries here, rather than by CSS counters, so that the
+// rendered text is what the reader sees.
+//
+// Every example is also registered under each of the SSR modes, at
+// `/ssr/{mode}/{slug}`, so that the behavior under the different modes may be
+// compared; `ssr_mode::select_ssr_mode` redirects there when the mode is
+// chosen through the `ssr` query parameter or the `ssr_mode` cookie.
+use leptos::{either::Either, prelude::*};
+use leptos_router::{
+    GeneratedRouteData, MatchNestedRoutes, NestedRoute, RouteMatchId, SsrMode,
+    StaticSegment,
+};
+
+/// The group of examples an example belongs to, where the examples of the
+/// walkthrough sections are numbered consecutively across the sections.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    /// Integration through `<script>` tags.
+    Script,
+    /// Integration by calling out to the library through `wasm-bindgen`.
+    WasmBindgen,
+    /// The features built on top of the integration, which are not part of
+    /// the walkthrough and so are not numbered.
+    Features,
+}
+
+impl Section {
+    pub fn numbered(self) -> bool {
+        !matches!(self, Section::Features)
+    }
+}
+
+pub struct Example {
+    /// The path of the example, without the leading `/`.
+    pub slug: &'static str,
+    /// The title, where the text within backticks is shown as code (as is
+    /// the case for the subtitle).
+    pub title: &'static str,
+    pub subtitle: &'static str,
+    pub section: Section,
+    /// Whether this is numbered as a sub-example of the preceding example.
+    pub sub: bool,
+    pub view: fn() -> AnyView,
+    pub ssr: SsrMode,
+    pub tags: &'static [&'static str],
+}
+
+/// The number (e.g. `5` or `5.1`) of each of the examples, if numbered.
+pub fn numbers(examples: &[Example]) -> Vec<Option<String>> {
+    let (mut number, mut sub) = (0, 0);
+    examples
+        .iter()
+        .map(|example| {
+            if !example.section.numbered() {
+                None
+            } else if example.sub {
+                sub += 1;
+                Some(format!("{number}.{sub}"))
+            } else {
+                number += 1;
+                sub = 0;
+                Some(number.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Whether the example is the last one of its section.
+pub fn ends_section(examples: &[Example], index: usize) -> bool {
+    examples
+        .get(index + 1)
+        .is_none_or(|next| next.section != examples[index].section)
+}
+
+/// The first segment of the paths of the examples registered under the
+/// individual SSR modes.
+pub const SSR_MODE_PREFIX: &str = "ssr";
+
+/// The SSR modes the examples are registered under, by their names as used
+/// in the paths.
+pub const SSR_MODES: [(&str, SsrMode); 4] = [
+    ("out-of-order", SsrMode::OutOfOrder),
+    ("partially-blocked", SsrMode::PartiallyBlocked),
+    ("in-order", SsrMode::InOrder),
+    ("async", SsrMode::Async),
+];
+
+/// The name of the SSR mode, as used in the paths.
+pub fn ssr_mode_name(mode: &SsrMode) -> &'static str {
+    SSR_MODES
+        .iter()
+        .find(|(_, m)| m == mode)
+        .map_or("static", |(name, _)| name)
+}
+
+/// The SSR mode (by name) with the rest of the path, for the path of an
+/// example registered under that mode.
+pub fn split_ssr_mode_path(path: &str) -> Option<(&'static str, &str)> {
+    let rest = path.strip_prefix('/')?.strip_prefix(SSR_MODE_PREFIX)?;
+    let (name, rest) = rest.strip_prefix('/')?.split_once('/')?;
+    let (name, _) = SSR_MODES.iter().find(|(n, _)| *n == name)?;
+    Some((name, rest))
+}
+
+/// The example at the path, along with the SSR mode it is rendered with
+/// when requested at that path.
+pub fn example_at<'a>(
+    examples: &'a [Example],
+    path: &str,
+) -> Option<(&'a Example, &'static str)> {
+    let (mode, slug) = match split_ssr_mode_path(path) {
+        Some((mode, slug)) => (Some(mode), slug),
+        None => (None, path.strip_prefix('/')?),
+    };
+    let example = examples.iter().find(|example| example.slug == slug)?;
+    Some((example, mode.unwrap_or_else(|| ssr_mode_name(&example.ssr))))
+}
+
+/// Split the (sub)title into the plain text and code parts, with the code
+/// parts flagged.
+pub fn title_parts(title: &str) -> impl Iterator<Item = (bool, &str)> {
+    title
+        .split('`')
+        .enumerate()
+        .map(|(n, part)| (n % 2 == 1, part))
+        .filter(|(_, part)| !part.is_empty())
+}
+
+type ExampleRoute =
+    NestedRoute<StaticSegment<&'static str>, (), (), fn() -> AnyView>;
+type ModeRoute = NestedRoute<
+    (
+        StaticSegment<&'static str>,
+        StaticSegment<&'static str>,
+        StaticSegment<&'static str>,
+    ),
+    (),
+    (),
+    fn() -> AnyView,
+>;
+
+/// The routes of the examples, in the order of the registry, followed by
+/// those under the individual SSR modes.
+#[derive(Clone, Debug)]
+pub struct ExampleRouteDefs {
+    examples: Vec<ExampleRoute>,
+    modes: Vec<ModeRoute>,
+}
+
+/// Define the routes of the `examples`, as a child of `FlatRoutes`.
+#[component(transparent)]
+pub fn ExampleRoutes(examples: &'static [Example]) -> ExampleRouteDefs {
+    ExampleRouteDefs {
+        examples: examples
+            .iter()
+            .map(|example| {
+                NestedRoute::new(StaticSegment(example.slug), example.view)
+                    .ssr_mode(example.ssr.clone())
+            })
+            .collect(),
+        modes: SSR_MODES
+            .iter()
+            .flat_map(|(name, mode)| {
+                examples.iter().map(|example| {
+                    NestedRoute::new(
+                        (
+                            StaticSegment(SSR_MODE_PREFIX),
+                            StaticSegment(*name),
+                            StaticSegment(example.slug),
+                        ),
+                        example.view,
+                    )
+                    .ssr_mode(mode.clone())
+                })
+            })
+            .collect(),
+    }
+}
+
+impl MatchNestedRoutes for ExampleRouteDefs {
+    type Data = ();
+    type Match = Either<
+        <ExampleRoute as MatchNestedRoutes>::Match,
+        <ModeRoute as MatchNestedRoutes>::Match,
+    >;
+
+    fn match_nested<'a>(
+        &'a self,
+        path: &'a str,
+    ) -> (Option<(RouteMatchId, Self::Match)>, &'a str) {
+        let examples = self.examples.iter().map(|route| {
+            let (matched, rest) = route.match_nested(path);
+            (matched.map(|(id, m)| (id, Either::Left(m))), rest)
+        });
+        let modes = self.modes.iter().map(|route| {
+            let (matched, rest) = route.match_nested(path);
+            (matched.map(|(id, m)| (id, Either::Right(m))), rest)
+        });
+        examples
+            .chain(modes)
+            .find(|(matched, _)| matched.is_some())
+            .unwrap_or((None, path))
+    }
+
+    fn generate_routes(
+        &self,
+    ) -> impl IntoIterator<Item = GeneratedRouteData> + '_ {
+        let examples = self.examples.iter().flat_map(|r| r.generate_routes());
+        let modes = self.modes.iter().flat_map(|r| r.generate_routes());
+        examples.chain(modes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::EXAMPLES;
+
+    #[test]
+    fn number_examples() {
+        // the number and whether it ends its section, for every example
+        let expected = [
+            ("naive", Some("1"), false),
+            ("naive-alt", Some("2"), false),
+            ("naive-hook", Some("3"), false),
+            ("naive-fallback", Some("4"), false),
+            ("signal-effect-script", Some("5"), false),
+            ("custom-event", Some("5.1"), true),
+            ("wasm-bindgen-naive", Some("6"), false),
+            ("wasm-bindgen-event", Some("7"), false),
+            ("wasm-bindgen-effect", Some("8"), false),
+            ("wasm-bindgen-direct", Some("9"), false),
+            ("wasm-bindgen-direct-fixed", Some("10"), true),
+            ("streaming", None, false),
+            ("editor", None, false),
+            ("markdown", None, false),
+            ("compare", None, false),
+            ("remote-highlight", None, true),
+        ];
+        let numbers = numbers(EXAMPLES);
+        let actual = EXAMPLES
+            .iter()
+            .enumerate()
+            .map(|(n, example)| {
+                (
+                    example.slug,
+                    numbers[n].as_deref(),
+                    ends_section(EXAMPLES, n),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn examples_at_paths() {
+        for (path, expected) in [
+            ("/naive", Some(("naive", "async"))),
+            ("/streaming", Some(("streaming", "out-of-order"))),
+            ("/ssr/in-order/naive", Some(("naive", "in-order"))),
+            ("/ssr/async/streaming", Some(("streaming", "async"))),
+            ("/ssr/sync/naive", None),
+            ("/ssr/in-order/missing", None),
+            ("/missing", None),
+            ("naive", None),
+        ] {
+            let actual = example_at(EXAMPLES, path)
+                .map(|(example, mode)| (example.slug, mode));
+            assert_eq!(actual, expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn split_titles() {
+        for (title, expected) in [
+            (
+                "Using `wasm-bindgen`",
+                &[(false, "Using "), (true, "wasm-bindgen")][..],
+            ),
+            ("Markdown", &[(false, "Markdown")]),
+            ("`<Script>`", &[(true, "<Script>")]),
+        ] {
+            assert_eq!(title_parts(title).collect::<Vec<_>>(), expected);
+        }
+    }
+}
@@ -0,0 +1,382 @@
+// Extraction of excerpts from code, selected by a line range (`L5-L12`) or
+// by a named region delimited by `ANCHOR: name` and `ANCHOR_END: name`
+// markers (much like mdBook's `{{#include}}`), as the fragment of the id
+// passed to `fetch_code` (e.g. `ch05_02a#L11-L13`).
+use crate::api::Segment;
+use std::{ops::RangeInclusive, str::FromStr};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Selection {
+    /// The 1-based, inclusive range of lines.
+    Lines(RangeInclusive<usize>),
+    /// The lines between the `ANCHOR` and `ANCHOR_END` markers of the name.
+    Anchor(String),
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let line = |s: &str| {
+            s.strip_prefix('L')
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|n| *n > 0)
+        };
+        // Anything that starts off like a line number is taken as a range,
+        // so that anchors may still contain `-`.
+        let is_range = s
+            .strip_prefix('L')
+            .is_some_and(|n| n.starts_with(|c: char| c.is_ascii_digit()));
+        if is_range {
+            let (start, end) = s.split_once('-').unwrap_or((s, s));
+            return match (line(start), line(end)) {
+                (Some(start), Some(end)) if start <= end => {
+                    Ok(Selection::Lines(start..=end))
+                }
+                _ => Err(format!("invalid line range `{s}`")),
+            };
+        }
+        if !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            Ok(Selection::Anchor(s.to_string()))
+        } else {
+            Err(format!("invalid anchor `{s}`"))
+        }
+    }
+}
+
+/// Split an id into the id proper and the selection in its fragment.
+pub fn split_id(id: &str) -> Result<(&str, Option<Selection>), String> {
+    match id.split_once('#') {
+        Some((id, fragment)) => Ok((id, Some(fragment.parse()?))),
+        None => Ok((id, None)),
+    }
+}
+
+/// The selected lines of some code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Excerpt {
+    /// The line number of the first shown line in the original code.
+    pub start_line: usize,
+    pub segments: Vec<Segment>,
+}
+
+fn marker<'a>(line: &'a str, kind: &str) -> Option<&'a str> {
+    let (_, rest) = line.split_once(kind)?;
+    Some(rest.trim())
+}
+
+fn is_marker(line: &str) -> bool {
+    marker(line, "ANCHOR:").is_some() || marker(line, "ANCHOR_END:").is_some()
+}
+
+// The mdBook convention of hiding lines in Rust code that start with `# `
+// (or consist solely of `#`), returning the line without that prefix.
+fn hidden_rust_line(line: &str) -> Option<String> {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+    if trimmed.trim_end() == "#" {
+        Some(format!("{indent}{}", &trimmed[1..]))
+    } else {
+        trimmed
+            .strip_prefix("# ")
+            .map(|rest| format!("{indent}{rest}"))
+    }
+}
+
+/// What becomes of a line of the code, as decided by `Selector`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Placement {
+    Shown(String),
+    Hidden(String),
+    /// An anchor marker, which is left out altogether.
+    Marker,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Anchor {
+    Before,
+    // along with the line following the marker
+    Within(usize),
+    After(usize),
+}
+
+/// Places the lines of some code one by one as they are read, as per the
+/// selection, such that the code may be excerpted without having it all.
+/// Anchor markers and (if `hide_prefixed` is set) the lines within the
+/// selection prefixed by `# ` are only handled when there is a selection.
+/// Only the markers that would leave a gap in the numbering of the shown
+/// lines are kept: within a line range they are shown like any other line,
+/// and those of other anchors within the selected one are hidden.
+#[derive(Clone, Debug)]
+pub struct Selector<'a> {
+    selection: Option<&'a Selection>,
+    hide_prefixed: bool,
+    anchor: Anchor,
+    start_line: Option<usize>,
+}
+
+impl<'a> Selector<'a> {
+    pub fn new(selection: Option<&'a Selection>, hide_prefixed: bool) -> Self {
+        Self {
+            selection,
+            hide_prefixed,
+            anchor: Anchor::Before,
+            start_line: None,
+        }
+    }
+
+    /// Place the `n`th (1-based) line of the code, which must be provided
+    /// in order.
+    pub fn place(&mut self, n: usize, line: &str) -> Placement {
+        let Some(selection) = self.selection else {
+            return Placement::Shown(line.to_string());
+        };
+        if is_marker(line) {
+            match selection {
+                // the lines asked for are shown as they are, markers and all
+                Selection::Lines(range) if range.contains(&n) => (),
+                Selection::Lines(_) => return Placement::Marker,
+                Selection::Anchor(name) => {
+                    let within = matches!(self.anchor, Anchor::Within(_));
+                    self.anchor = match self.anchor {
+                        Anchor::Before
+                            if marker(line, "ANCHOR:") == Some(name) =>
+                        {
+                            Anchor::Within(n + 1)
+                        }
+                        Anchor::Within(start)
+                            if marker(line, "ANCHOR_END:") == Some(name) =>
+                        {
+                            Anchor::After(start)
+                        }
+                        anchor => anchor,
+                    };
+                    // the markers of the other anchors within the selection
+                    // are hidden rather than removed, so that the lines
+                    // after them are still numbered as in the original
+                    if within && matches!(self.anchor, Anchor::Within(_)) {
+                        return Placement::Hidden(line.to_string());
+                    }
+                    return Placement::Marker;
+                }
+            }
+        }
+        let selected = match selection {
+            Selection::Lines(range) => range.contains(&n),
+            Selection::Anchor(_) => matches!(self.anchor, Anchor::Within(_)),
+        };
+        if !selected {
+            return Placement::Hidden(line.to_string());
+        }
+        match hidden_rust_line(line).filter(|_| self.hide_prefixed) {
+            Some(text) => Placement::Hidden(text),
+            None => {
+                self.start_line.get_or_insert(n);
+                Placement::Shown(line.to_string())
+            }
+        }
+    }
+
+    /// The line number of the first shown line, once all of the `lines` of
+    /// the code were placed, or `None` should the selection not be
+    /// present in the code.
+    pub fn start_line(&self, lines: usize) -> Option<usize> {
+        let fallback = match (self.selection, self.anchor) {
+            (None, _) => 1,
+            (Some(Selection::Lines(range)), _) if *range.end() > lines => {
+                return None
+            }
+            (Some(Selection::Lines(range)), _) => *range.start(),
+            (Some(Selection::Anchor(_)), Anchor::Before) => return None,
+            (
+                Some(Selection::Anchor(_)),
+                Anchor::Within(start) | Anchor::After(start),
+            ) => start,
+        };
+        Some(self.start_line.unwrap_or(fallback))
+    }
+}
+
+/// Extract the selected lines from the code, returning `None` should the
+/// selection not be present.  Lines outside of the selection are provided
+/// as hidden segments surrounding the selected ones, and the anchor markers
+/// are removed (but for those kept by `Selector`).  If `hide_prefixed` is
+/// set, the lines within the selection that are prefixed by `# ` are also
+/// hidden.
+///
+/// Without a selection, the code is left as it is in its entirety, as the
+/// markers and prefixes are only meaningful to those who asked for them.
+pub fn extract(
+    code: &str,
+    selection: Option<&Selection>,
+    hide_prefixed: bool,
+) -> Option<Excerpt> {
+    if selection.is_none() {
+        return Some(Excerpt {
+            start_line: 1,
+            segments: vec![Segment::Shown(code.to_string())],
+        });
+    }
+    let mut selector = Selector::new(selection, hide_prefixed);
+    let mut segments = Vec::<Segment>::new();
+    let mut lines = 0;
+    for (n, line) in (1..).zip(code.split_inclusive('\n')) {
+        lines = n;
+        let (shown, text) = match selector.place(n, line) {
+            Placement::Shown(text) => (true, text),
+            Placement::Hidden(text) => (false, text),
+            Placement::Marker => continue,
+        };
+        match (segments.last_mut(), shown) {
+            (Some(Segment::Shown(s)), true)
+            | (Some(Segment::Hidden(s)), false) => s.push_str(&text),
+            (_, true) => segments.push(Segment::Shown(text)),
+            (_, false) => segments.push(Segment::Hidden(text)),
+        }
+    }
+    Some(Excerpt {
+        start_line: selector.start_line(lines)?,
+        segments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "\
+fn main() {
+    // ANCHOR: body
+    let x = 1;
+    # let y = 2;
+    println!(\"{x}\");
+    // ANCHOR_END: body
+}
+";
+
+    #[test]
+    fn parse_selection() {
+        assert_eq!("L5".parse(), Ok(Selection::Lines(5..=5)));
+        assert_eq!("L5-L12".parse(), Ok(Selection::Lines(5..=12)));
+        assert_eq!("body".parse(), Ok(Selection::Anchor("body".to_string())));
+        assert_eq!("a-b".parse(), Ok(Selection::Anchor("a-b".to_string())));
+        for invalid in ["L0", "L12-L5", "L1-", "", "a b", "L1-Lx"] {
+            assert!(invalid.parse::<Selection>().is_err(), "{invalid}");
+        }
+        assert_eq!(split_id("main.rs"), Ok(("main.rs", None)));
+        assert_eq!(
+            split_id("main.rs#L2"),
+            Ok(("main.rs", Some(Selection::Lines(2..=2))))
+        );
+    }
+
+    #[test]
+    fn extract_line_range() {
+        let excerpt =
+            extract(CODE, Some(&Selection::Lines(3..=5)), false).unwrap();
+        assert_eq!(excerpt.start_line, 3);
+        assert_eq!(
+            excerpt.segments,
+            vec![
+                Segment::Hidden("fn main() {\n".to_string()),
+                Segment::Shown(
+                    "    let x = 1;\n    # let y = 2;\n    println!(\"{x}\");\n"
+                        .to_string()
+                ),
+                Segment::Hidden("}\n".to_string()),
+            ]
+        );
+        assert_eq!(extract(CODE, Some(&Selection::Lines(7..=8)), false), None);
+    }
+
+    #[test]
+    fn extract_nothing_without_selection() {
+        for hide_prefixed in [false, true] {
+            assert_eq!(
+                extract(CODE, None, hide_prefixed),
+                Some(Excerpt {
+                    start_line: 1,
+                    segments: vec![Segment::Shown(CODE.to_string())],
+                })
+            );
+        }
+        let markdown = "# Title\n\n<!-- ANCHOR: a -->\ntext\n";
+        assert_eq!(
+            extract(markdown, None, false).unwrap().segments,
+            vec![Segment::Shown(markdown.to_string())]
+        );
+    }
+
+    #[test]
+    fn extract_anchor_hiding_prefixed() {
+        let selection = Selection::Anchor("body".to_string());
+        let excerpt = extract(CODE, Some(&selection), true).unwrap();
+        assert_eq!(excerpt.start_line, 3);
+        assert_eq!(
+            excerpt.segments,
+            vec![
+                Segment::Hidden("fn main() {\n".to_string()),
+                Segment::Shown("    let x = 1;\n".to_string()),
+                Segment::Hidden("    let y = 2;\n".to_string()),
+                Segment::Shown("    println!(\"{x}\");\n".to_string()),
+                Segment::Hidden("}\n".to_string()),
+            ]
+        );
+        let missing = Selection::Anchor("missing".to_string());
+        assert_eq!(extract(CODE, Some(&missing), true), None);
+    }
+
+    #[test]
+    fn extract_line_range_spanning_marker() {
+        let excerpt =
+            extract(CODE, Some(&Selection::Lines(2..=5)), true).unwrap();
+        assert_eq!(excerpt.start_line, 2);
+        assert_eq!(
+            excerpt.segments,
+            vec![
+                Segment::Hidden("fn main() {\n".to_string()),
+                Segment::Shown(
+                    "    // ANCHOR: body\n    let x = 1;\n".to_string()
+                ),
+                Segment::Hidden("    let y = 2;\n".to_string()),
+                Segment::Shown("    println!(\"{x}\");\n".to_string()),
+                Segment::Hidden("}\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_anchor_keeping_numbering() {
+        let code = "\
+// ANCHOR: outer
+# use std::fmt;
+fn main() {
+    // ANCHOR: inner
+    let x = 1;
+    // ANCHOR_END: inner
+}
+// ANCHOR_END: outer
+";
+        let outer = Selection::Anchor("outer".to_string());
+        let excerpt = extract(code, Some(&outer), true).unwrap();
+        // the first shown line, after the hidden one
+        assert_eq!(excerpt.start_line, 3);
+        assert_eq!(
+            excerpt.segments,
+            vec![
+                Segment::Hidden("use std::fmt;\n".to_string()),
+                Segment::Shown("fn main() {\n".to_string()),
+                Segment::Hidden("    // ANCHOR: inner\n".to_string()),
+                Segment::Shown("    let x = 1;\n".to_string()),
+                Segment::Hidden("    // ANCHOR_END: inner\n".to_string()),
+                Segment::Shown("}\n".to_string()),
+            ]
+        );
+        let inner = Selection::Anchor("inner".to_string());
+        let excerpt = extract(code, Some(&inner), true).unwrap();
+        assert_eq!(excerpt.start_line, 5);
+    }
+}
@@ -0,0 +1,518 @@
+// A small server-side syntax highlighter approximating the markup of
+// highlight.js: it uses the same `<span class="hljs-keyword">` (and so on)
+// classes, such that the theme stylesheets under `/hljs/styles` apply to it
+// as is, but it won't tokenize code the way highlight.js does.  This is far
+// from a complete implementation of the grammars of highlight.js; it is a
+// lexer that recognizes the comments, strings, numbers and the keywords of
+// the languages, which covers the bulk of what is visible in a theme.
+//
+// All text is HTML escaped, with the only markup produced being the spans
+// for the classes, so the output is safe to be used as `inner_html`.
+use std::collections::BTreeSet;
+
+/// The highlighted markup along with the classes used in it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Highlighted {
+    pub html: String,
+    pub classes: BTreeSet<&'static str>,
+}
+
+struct Grammar {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    keywords: &'static [&'static str],
+    literals: &'static [&'static str],
+    types: &'static [&'static str],
+    // Rust specific constructs: lifetimes, raw strings, macros and
+    // attributes.
+    rust: bool,
+}
+
+const RUST: Grammar = Grammar {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn",
+        "else", "enum", "extern", "fn", "for", "if", "impl", "in", "let",
+        "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self",
+        "Self", "static", "struct", "super", "trait", "type", "unsafe", "use",
+        "where", "while", "yield",
+    ],
+    literals: &["true", "false", "Some", "None", "Ok", "Err"],
+    types: &[
+        "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64",
+        "u128", "usize", "f32", "f64", "bool", "char", "str", "String", "Vec",
+        "Option", "Result", "Box",
+    ],
+    rust: true,
+};
+
+const JAVASCRIPT: Grammar = Grammar {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\'', '`'],
+    keywords: &[
+        "as",
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "export",
+        "extends",
+        "finally",
+        "for",
+        "from",
+        "function",
+        "if",
+        "import",
+        "in",
+        "instanceof",
+        "interface",
+        "let",
+        "new",
+        "of",
+        "return",
+        "static",
+        "switch",
+        "this",
+        "throw",
+        "try",
+        "type",
+        "typeof",
+        "var",
+        "void",
+        "while",
+        "yield",
+    ],
+    literals: &["true", "false", "null", "undefined", "NaN", "Infinity"],
+    types: &["string", "number", "boolean", "any", "unknown", "never"],
+    rust: false,
+};
+
+const C: Grammar = Grammar {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+    keywords: &[
+        "auto",
+        "break",
+        "case",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "enum",
+        "extern",
+        "for",
+        "goto",
+        "if",
+        "inline",
+        "namespace",
+        "new",
+        "private",
+        "protected",
+        "public",
+        "return",
+        "sizeof",
+        "static",
+        "struct",
+        "switch",
+        "template",
+        "this",
+        "typedef",
+        "union",
+        "using",
+        "virtual",
+        "volatile",
+        "while",
+    ],
+    literals: &["true", "false", "NULL", "nullptr"],
+    types: &[
+        "bool", "char", "double", "float", "int", "long", "short", "signed",
+        "unsigned", "void", "size_t",
+    ],
+    rust: false,
+};
+
+const GO: Grammar = Grammar {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\'', '`'],
+    keywords: &[
+        "break",
+        "case",
+        "chan",
+        "const",
+        "continue",
+        "default",
+        "defer",
+        "else",
+        "fallthrough",
+        "for",
+        "func",
+        "go",
+        "goto",
+        "if",
+        "import",
+        "interface",
+        "map",
+        "package",
+        "range",
+        "return",
+        "select",
+        "struct",
+        "switch",
+        "type",
+        "var",
+    ],
+    literals: &["true", "false", "nil", "iota"],
+    types: &[
+        "bool", "byte", "error", "float32", "float64", "int", "int8", "int16",
+        "int32", "int64", "rune", "string", "uint", "uint8", "uint16",
+        "uint32", "uint64",
+    ],
+    rust: false,
+};
+
+const PYTHON: Grammar = Grammar {
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue",
+        "def", "del", "elif", "else", "except", "finally", "for", "from",
+        "global", "if", "import", "in", "is", "lambda", "nonlocal", "not",
+        "or", "pass", "raise", "return", "try", "while", "with", "yield",
+    ],
+    literals: &["True", "False", "None"],
+    types: &[
+        "int", "float", "str", "bytes", "list", "dict", "set", "tuple",
+    ],
+    rust: false,
+};
+
+const BASH: Grammar = Grammar {
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "fi", "for", "function",
+        "if", "in", "local", "return", "then", "until", "while",
+    ],
+    literals: &["true", "false"],
+    types: &[],
+    rust: false,
+};
+
+fn grammar(lang: &str) -> Option<&'static Grammar> {
+    match lang {
+        "rust" => Some(&RUST),
+        "javascript" | "typescript" => Some(&JAVASCRIPT),
+        "c" | "cpp" => Some(&C),
+        "go" => Some(&GO),
+        "python" => Some(&PYTHON),
+        "bash" => Some(&BASH),
+        _ => None,
+    }
+}
+
+/// Whether the `lang` is one that `highlight` provides markup for, rather
+/// than simply escaping the code.
+pub fn supports(lang: &str) -> bool {
+    grammar(lang).is_some()
+}
+
+struct Writer {
+    highlighted: Highlighted,
+}
+
+impl Writer {
+    fn text(&mut self, text: &str) {
+        html_escape::encode_text_to_string(text, &mut self.highlighted.html);
+    }
+
+    fn span(&mut self, class: &'static str, text: &str) {
+        self.highlighted.classes.insert(class);
+        self.highlighted.html.push_str("<span class=\"");
+        self.highlighted.html.push_str(class);
+        self.highlighted.html.push_str("\">");
+        self.text(text);
+        self.highlighted.html.push_str("</span>");
+    }
+}
+
+/// Highlight the `code` as the `lang` (going by the names highlight.js
+/// uses), with the code simply escaped for the unsupported languages.
+pub fn highlight(code: &str, lang: &str) -> Highlighted {
+    let mut out = Writer {
+        highlighted: Highlighted::default(),
+    };
+    let Some(grammar) = grammar(lang) else {
+        out.text(code);
+        return out.highlighted;
+    };
+
+    let mut rest = code;
+    // whether the previous token (other than whitespace) was `fn` (or its
+    // equivalent), such that the next identifier is the name of a function
+    let mut title_next = false;
+    while let Some(c) = rest.chars().next() {
+        // anything but whitespace ends the wait for the title, e.g. for the
+        // anonymous `function (x)` where `x` isn't a title
+        let title = std::mem::take(&mut title_next);
+        let len = if let Some(len) = comment_len(grammar, rest) {
+            out.span("hljs-comment", &rest[..len]);
+            len
+        } else if let Some(len) = string_len(grammar, rest) {
+            out.span("hljs-string", &rest[..len]);
+            len
+        } else if grammar.rust && c == '\'' {
+            let len = lifetime_len(rest);
+            let class = if rest[..len].ends_with('\'') {
+                "hljs-string"
+            } else {
+                "hljs-symbol"
+            };
+            out.span(class, &rest[..len]);
+            len
+        } else if grammar.rust
+            && (rest.starts_with("#[") || rest.starts_with("#!["))
+        {
+            let len = rest.find(']').map_or(rest.len(), |n| n + 1);
+            out.span("hljs-meta", &rest[..len]);
+            len
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            // include the fractional part of floats
+            let len = match rest[len..].strip_prefix('.') {
+                Some(frac)
+                    if frac.starts_with(|c: char| c.is_ascii_digit()) =>
+                {
+                    len + 1
+                        + frac
+                            .find(|c: char| {
+                                !(c.is_ascii_alphanumeric() || c == '_')
+                            })
+                            .unwrap_or(frac.len())
+                }
+                _ => len,
+            };
+            out.span("hljs-number", &rest[..len]);
+            len
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            let mut len = len;
+            if title {
+                out.span("hljs-title", word);
+            } else if grammar.keywords.contains(&word) {
+                out.span("hljs-keyword", word);
+                title_next = matches!(word, "fn" | "function" | "def" | "func");
+            } else if grammar.literals.contains(&word) {
+                out.span("hljs-literal", word);
+            } else if grammar.types.contains(&word) {
+                out.span("hljs-type", word);
+            } else if grammar.rust && rest[len..].starts_with('!') {
+                // a macro invocation, e.g. `println!`
+                len += 1;
+                out.span("hljs-built_in", &rest[..len]);
+            } else {
+                out.text(word);
+            }
+            len
+        } else {
+            title_next = title && c.is_whitespace();
+            out.text(&rest[..c.len_utf8()]);
+            c.len_utf8()
+        };
+        rest = &rest[len..];
+    }
+    out.highlighted
+}
+
+fn comment_len(grammar: &Grammar, s: &str) -> Option<usize> {
+    if grammar.line_comments.iter().any(|p| s.starts_with(p)) {
+        // Rust attributes also start with `#`, but Rust has no `#` comments
+        return Some(s.find('\n').unwrap_or(s.len()));
+    }
+    let (open, close) = grammar.block_comment?;
+    if !s.starts_with(open) {
+        return None;
+    }
+    // Rust permits nesting of block comments
+    let mut depth = 0;
+    let mut i = 0;
+    while i < s.len() {
+        if s[i..].starts_with(open) && (grammar.rust || depth == 0) {
+            depth += 1;
+            i += open.len();
+        } else if s[i..].starts_with(close) {
+            depth -= 1;
+            i += close.len();
+            if depth == 0 {
+                return Some(i);
+            }
+        } else {
+            i += s[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    Some(s.len())
+}
+
+fn string_len(grammar: &Grammar, s: &str) -> Option<usize> {
+    if grammar.rust {
+        let unprefixed = s
+            .strip_prefix("br")
+            .or_else(|| s.strip_prefix('r'))
+            .filter(|r| r.starts_with(['"', '#']));
+        if let Some(raw) = unprefixed {
+            let hashes = raw.len() - raw.trim_start_matches('#').len();
+            let body = raw[hashes..].strip_prefix('"')?;
+            let close = format!("\"{}", "#".repeat(hashes));
+            let prefix = s.len() - raw.len() + hashes + 1;
+            return Some(
+                body.find(&close)
+                    .map_or(s.len(), |n| prefix + n + close.len()),
+            );
+        }
+        if let Some(body) = s.strip_prefix('b').filter(|b| b.starts_with('"')) {
+            return quoted_len(body, '"').map(|n| n + 1);
+        }
+    }
+    let quote = s.chars().next().filter(|c| grammar.quotes.contains(c))?;
+    quoted_len(s, quote)
+}
+
+// The length of the string starting with `quote`, including the closing
+// quote and honoring backslash escapes.
+fn quoted_len(s: &str, quote: char) -> Option<usize> {
+    let mut chars = s.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == quote {
+            return Some(i + c.len_utf8());
+        }
+    }
+    Some(s.len())
+}
+
+// Disambiguate between a lifetime (e.g. `'a`) and a character literal (e.g.
+// `'a'` or `'\n'`), which starts at `s`.
+fn lifetime_len(s: &str) -> usize {
+    let body = &s[1..];
+    if let Some(escaped) = body.strip_prefix('\\') {
+        return escaped.find('\'').map_or(s.len(), |n| n + 3);
+    }
+    let mut chars = body.char_indices();
+    match (chars.next(), chars.next()) {
+        (Some(_), Some((i, '\''))) => i + 2,
+        _ => {
+            1 + body
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(body.len())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlight_rust() {
+        let highlighted = highlight(
+            "#[derive(Debug)]\nfn area<'a>(w: u32) -> u32 {\n    \
+             // <b>\n    println!(\"{} & {}\", 'x', 1.5);\n}\n",
+            "rust",
+        );
+        assert_eq!(
+            highlighted.html,
+            "<span class=\"hljs-meta\">#[derive(Debug)]</span>\n\
+             <span class=\"hljs-keyword\">fn</span> \
+             <span class=\"hljs-title\">area</span>&lt;\
+             <span class=\"hljs-symbol\">'a</span>&gt;(w: \
+             <span class=\"hljs-type\">u32</span>) -&gt; \
+             <span class=\"hljs-type\">u32</span> {\n    \
+             <span class=\"hljs-comment\">// &lt;b&gt;</span>\n    \
+             <span class=\"hljs-built_in\">println!</span>(\
+             <span class=\"hljs-string\">\"{} &amp; {}\"</span>, \
+             <span class=\"hljs-string\">'x'</span>, \
+             <span class=\"hljs-number\">1.5</span>);\n}\n"
+        );
+        assert_eq!(
+            highlighted.classes.into_iter().collect::<Vec<_>>(),
+            [
+                "hljs-built_in",
+                "hljs-comment",
+                "hljs-keyword",
+                "hljs-meta",
+                "hljs-number",
+                "hljs-string",
+                "hljs-symbol",
+                "hljs-title",
+                "hljs-type",
+            ]
+        );
+    }
+
+    #[test]
+    fn highlight_rust_strings_and_comments() {
+        let html = |code| highlight(code, "rust").html;
+        assert_eq!(
+            html("r#\"a \" b\"# x"),
+            "<span class=\"hljs-string\">r#\"a \" b\"#</span> x"
+        );
+        assert_eq!(
+            html("/* a /* b */ c */ x"),
+            "<span class=\"hljs-comment\">/* a /* b */ c */</span> x"
+        );
+        assert_eq!(
+            html("\"unterminated"),
+            "<span class=\"hljs-string\">\"unterminated</span>"
+        );
+    }
+
+    #[test]
+    fn titles_only_follow_fn() {
+        let html = |code| highlight(code, "javascript").html;
+        assert_eq!(
+            html("function  f(x)"),
+            "<span class=\"hljs-keyword\">function</span>  \
+             <span class=\"hljs-title\">f</span>(x)"
+        );
+        // anonymous functions have no title
+        assert_eq!(
+            html("function (x)"),
+            "<span class=\"hljs-keyword\">function</span> (x)"
+        );
+        assert_eq!(
+            html("function(x)"),
+            "<span class=\"hljs-keyword\">function</span>(x)"
+        );
+    }
+
+    #[test]
+    fn unsupported_lang_is_escaped() {
+        let highlighted = highlight("<script>alert(1)</script>", "cobol");
+        assert_eq!(highlighted.html, "&lt;script&gt;alert(1)&lt;/script&gt;");
+        assert!(highlighted.classes.is_empty());
+    }
+}
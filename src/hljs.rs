@@ -21,23 +21,57 @@ mod csr {
 
         #[wasm_bindgen(js_namespace = default, js_name = highlightAll)]
         pub fn highlight_all();
+
+        /// The names of the languages registered with highlight.js.
+        #[wasm_bindgen(js_namespace = default, js_name = listLanguages)]
+        pub fn languages() -> Vec<String>;
     }
 
-    // Keeping the `ignoreIllegals` argument out of the default case, and since there is no optional arguments
-    // in Rust, this will have to be provided in a separate function (e.g. `highlight_ignore_illegals`), much
-    // like how `web_sys` does it for the browser APIs.  For simplicity, only the highlighted HTML code is
-    // returned on success, and None on error.
+    // Keeping the `ignoreIllegals` argument out of the default case, and
+    // since there is no optional arguments in Rust, this is provided as a
+    // separate function (i.e. `highlight_ignore_illegals`), much like how
+    // `web_sys` does it for the browser APIs.  Upon any illegal syntax for
+    // the language, the default gives up on the highlighting and returns the
+    // code merely escaped.  For simplicity, only the highlighted HTML code is
+    // returned on success, and None on error (e.g. an unknown language).
     pub fn highlight(code: String, lang: String) -> Option<String> {
+        highlight_with(code, lang, false)
+    }
+
+    // Carry on highlighting past any illegal syntax, e.g. for code that is
+    // still being written.
+    pub fn highlight_ignore_illegals(
+        code: String,
+        lang: String,
+    ) -> Option<String> {
+        highlight_with(code, lang, true)
+    }
+
+    fn highlight_with(
+        code: String,
+        lang: String,
+        ignore_illegals: bool,
+    ) -> Option<String> {
+        let (start, bytes) = (crate::telemetry::now(), code.len());
         let options = js_sys::Object::new();
-        set(&options, &"language".into(), &lang.into())
+        set(&options, &"language".into(), &lang.as_str().into())
             .expect("failed to assign lang to options");
-        highlight_lang(code, options)
+        set(&options, &"ignoreIllegals".into(), &ignore_illegals.into())
+            .expect("failed to assign ignoreIllegals to options");
+        let result = highlight_lang(code, options)
             .map(|result| {
                 let value = get(&result, &"value".into())
                     .expect("HighlightResult failed to contain the value key");
                 value.into_serde().expect("Value should have been a string")
             })
-            .ok()
+            .ok();
+        crate::telemetry::record_highlight(
+            start,
+            &lang,
+            bytes,
+            result.is_some(),
+        );
+        result
     }
 }
 
@@ -0,0 +1,275 @@
+// Artificial latency for the loading of `highlight.js`, to help reproduce
+// the race conditions demonstrated by the examples.  The delay applied to
+// each load is taken from a cycle of values, unless fixed by the client
+// through the `hljs_latency` cookie (e.g. by the end-to-end tests), which is
+// only honoured along with the race gates (`RACE_CONTROL=1`), as it has the
+// server hold the response for as long as the client asks.  When
+// SRI is switched off for development (the `no-sri` feature), the script is
+// also made to log the delay that was used along with every call to
+// `highlightAll()`, so that what happened may be followed from the console.
+use crate::{
+    assets::{CacheMode, Encoding},
+    race::{self, Gate},
+    server_timing::ServerTiming,
+};
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{
+        header::{self, HeaderMap, HeaderValue},
+        StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http_body_util::BodyExt;
+use std::sync::{Mutex, OnceLock};
+
+pub static LATENCY: OnceLock<
+    Mutex<std::iter::Cycle<std::slice::Iter<'_, u64>>>,
+> = OnceLock::new();
+pub static ES_LATENCY: OnceLock<
+    Mutex<std::iter::Cycle<std::slice::Iter<'_, u64>>>,
+> = OnceLock::new();
+
+/// The cookie through which the latency (in milliseconds) of the standard
+/// `highlight.js` may be fixed for the requests of a client.
+pub const LATENCY_COOKIE: &str = "hljs_latency";
+
+/// The largest latency that may be requested through `LATENCY_COOKIE`.
+pub const MAX_REQUESTED_LATENCY: u64 = 10_000;
+
+// The latency requested through the cookie, if any.
+fn requested_latency(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| {
+            pair.trim().strip_prefix(LATENCY_COOKIE)?.strip_prefix('=')
+        })
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(|delay| delay.min(MAX_REQUESTED_LATENCY))
+}
+
+pub async fn latency_for_highlight_js(
+    req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let uri_parts = &mut req.uri().path().rsplit('/');
+
+    let is_highlightjs = uri_parts.next() == Some("highlight.min.js");
+    let es = uri_parts.next() == Some("es");
+    let module_type = if es { "es module " } else { "standard " };
+    let requested = if es || !race::enabled() {
+        None
+    } else {
+        requested_latency(req.headers())
+    };
+    let timing = req.extensions().get::<ServerTiming>().cloned();
+    if is_highlightjs && !es {
+        race::wait(req.headers(), Gate::Hljs).await;
+    }
+    let res = next.run(req).await;
+    // Responses from asset routes set up for production caching must be
+    // left alone, as any tampering will invalidate the validators.
+    let cacheable =
+        res.extensions().get::<CacheMode>() == Some(&CacheMode::Immutable);
+    if is_highlightjs && !cacheable {
+        // additional processing if the filename is the test subject
+        let (mut parts, body) = res.into_parts();
+        let bytes = body
+            .collect()
+            .await
+            .map_err(|err| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("error reading body: {err}"),
+                )
+            })?
+            .to_bytes();
+        let latency = if es { &ES_LATENCY } else { &LATENCY };
+
+        let delay = match requested {
+            Some(delay) => delay,
+            None => match latency
+                .get()
+                .expect("latency cycle wasn't set up")
+                .try_lock()
+            {
+                Ok(ref mut mutex) => {
+                    *mutex.next().expect("cycle always has next")
+                }
+                Err(_) => 0,
+            },
+        };
+
+        tracing::info!(
+            module_type = module_type.trim_end(),
+            delay_ms = delay,
+            "loading highlight.min.js with injected latency"
+        );
+        let duration = std::time::Duration::from_millis(delay);
+        tokio::time::sleep(duration).await;
+        if let Some(timing) = timing {
+            timing.record("injected-latency", duration);
+        }
+        // Appending anything to the script will invalidate its integrity
+        // hash, so it may only be injected if SRI is switched off.
+        if !cfg!(feature = "no-sri") {
+            return Ok(Response::from_parts(parts, bytes.into()));
+        }
+
+        // inject the logging of the delay used into the target script, and
+        // have every call to `highlightAll()` logged (the ES module gets at
+        // its own default export by importing itself)
+        let (import, hljs) = if es {
+            ("import __hljs from './highlight.min.js';\n", "__hljs")
+        } else {
+            ("", "hljs")
+        };
+        let js_log = format!(
+            "\nconsole.log('loaded {module_type}highlight.js with a \
+             minimum latency of {delay} ms');\n\
+             {import}{hljs}.highlightAll = ((highlightAll) => function() {{\n  \
+             console.log('{module_type}highlight.js: highlightAll() called');\n  \
+             return highlightAll.apply(this, arguments);\n\
+             }})({hljs}.highlightAll);"
+        );
+
+        // The log can only be appended to the decoded script, so undo
+        // and then redo any compression that was applied.
+        let encoding = Encoding::from_headers(&parts.headers);
+        let bytes = match encoding {
+            Some(encoding) => encoding.decode(&bytes),
+            None => Ok(bytes.into()),
+        }
+        .and_then(|bytes| {
+            let bytes = [bytes, js_log.into()].concat();
+            match encoding {
+                Some(encoding) => encoding.encode(&bytes, true),
+                None => Ok(bytes),
+            }
+        })
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("error processing body: {err}"),
+            )
+        })?;
+        let length = bytes.len();
+        let body = Body::from(bytes);
+
+        // Provide the bare minimum set of headers to avoid browser cache.
+        let mut headers = header::HeaderMap::from_iter([
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/javascript"),
+            ),
+            (header::CONTENT_LENGTH, HeaderValue::from(length)),
+            (header::VARY, HeaderValue::from_static("accept-encoding")),
+        ]);
+        if let Some(encoding) = encoding {
+            headers.insert(header::CONTENT_ENCODING, encoding.header_value());
+        }
+        parts.headers = headers;
+        Ok(Response::from_parts(parts, body))
+    } else {
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::HIGHLIGHT_MIN_JS_SRI;
+    use axum::{middleware, routing::get, Router};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use sha2::{Digest, Sha384};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn injected_log_respects_sri() {
+        LATENCY.get_or_init(|| [0].iter().cycle().into());
+        let app = Router::new()
+            .route(
+                "/highlight.min.js",
+                get(|| async {
+                    include_str!(
+                        "../node_modules/@highlightjs/cdn-assets/highlight.min.js"
+                    )
+                }),
+            )
+            .layer(middleware::from_fn(latency_for_highlight_js));
+        let res = app
+            .oneshot(
+                Request::get("/highlight.min.js")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let integrity =
+            format!("sha384-{}", STANDARD.encode(Sha384::digest(&body)));
+
+        if !cfg!(feature = "no-sri") {
+            assert_eq!(integrity, HIGHLIGHT_MIN_JS_SRI);
+        } else {
+            assert_ne!(integrity, HIGHLIGHT_MIN_JS_SRI);
+            assert!(String::from_utf8_lossy(&body).contains(
+                "console.log('loaded standard highlight.js with a minimum \
+                 latency of 0 ms');"
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn latency_requested_by_cookie() {
+        let headers = |cookie: &str| {
+            HeaderMap::from_iter([(
+                header::COOKIE,
+                HeaderValue::from_str(cookie).unwrap(),
+            )])
+        };
+        assert_eq!(requested_latency(&HeaderMap::new()), None);
+        assert_eq!(
+            requested_latency(&headers("a=1; hljs_latency=40")),
+            Some(40)
+        );
+        assert_eq!(requested_latency(&headers("hljs_latency=x")), None);
+        assert_eq!(
+            requested_latency(&headers("hljs_latency=99999999")),
+            Some(MAX_REQUESTED_LATENCY)
+        );
+
+        LATENCY.get_or_init(|| [0].iter().cycle().into());
+        let app = Router::new()
+            .route("/highlight.min.js", get(|| async { "var hljs = {};" }))
+            .layer(middleware::from_fn(latency_for_highlight_js));
+        // the cookie is ignored unless the race gates are enabled
+        let delay = if race::enabled() { 4 } else { 0 };
+        let res = app
+            .oneshot(
+                Request::get("/highlight.min.js")
+                    .header(header::COOKIE, "hljs_latency=4")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let body = String::from_utf8_lossy(&body);
+        if cfg!(feature = "no-sri") {
+            assert!(
+                body.contains(&format!("with a minimum latency of {delay} ms"))
+            );
+            assert!(
+                body.contains("standard highlight.js: highlightAll() called")
+            );
+        } else {
+            assert_eq!(body, "var hljs = {};");
+        }
+    }
+}
@@ -1,7 +1,40 @@
+// the view types of the larger demo pages are deeply nested
+#![recursion_limit = "256"]
+
 pub mod api;
 pub mod app;
+#[cfg(feature = "ssr")]
+pub mod assets;
+pub mod batch;
+pub mod clipboard;
+pub mod compare;
 pub mod consts;
+pub mod examples;
+pub mod excerpt;
+#[cfg(feature = "ssr")]
+pub mod highlight;
 pub mod hljs;
+#[cfg(feature = "ssr")]
+pub mod latency;
+pub mod lines;
+pub mod markdown;
+#[cfg(feature = "ssr")]
+pub mod race;
+#[cfg(feature = "ssr")]
+pub mod sandbox;
+pub mod sanitize;
+#[cfg(feature = "ssr")]
+pub mod server;
+#[cfg(feature = "ssr")]
+pub mod server_timing;
+#[cfg(feature = "ssr")]
+pub mod source;
+#[cfg(feature = "ssr")]
+pub mod ssr_mode;
+pub mod telemetry;
+pub mod theme;
+#[cfg(feature = "ssr")]
+pub mod trace;
 
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
@@ -15,10 +48,14 @@ pub fn hydrate() {
         // a restart of the application is required to continue.
         console_error_panic_hook::hook(info);
         let window = leptos::prelude::window();
-        if !matches!(
+        let hydrated = matches!(
             js_sys::Reflect::get(&window, &wasm_bindgen::JsValue::from_str(LEPTOS_HYDRATED)),
             Ok(t) if t == true
-        ) {
+        );
+        // reported right away, as the page may well be unusable from here
+        telemetry::record_panic(info, hydrated);
+        telemetry::flush();
+        if !hydrated {
             let document = leptos::prelude::document();
             let _ = document.query_selector("#reset").map(|el| {
                 el.map(|el| {
@@ -32,7 +69,18 @@ pub fn hydrate() {
             });
         }
     }));
+    telemetry::flush_on_exit();
+    let start = telemetry::now();
+    if let Some(at) = start {
+        telemetry::record(telemetry::Event::HydrationStart { at });
+    }
     leptos::mount::hydrate_body(App);
+    if let (Some(start), Some(at)) = (start, telemetry::now()) {
+        telemetry::record(telemetry::Event::HydrationEnd {
+            at,
+            duration: at - start,
+        });
+    }
 
     let window = leptos::prelude::window();
     js_sys::Reflect::set(
@@ -48,4 +96,5 @@ pub fn hydrate() {
         .dispatch_event(&event)
         .expect("error dispatching hydrated event");
     leptos::logging::log!("dispatched hydrated event");
+    telemetry::flush();
 }
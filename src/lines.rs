@@ -0,0 +1,215 @@
+// Support for rendering code line by line (for line numbers, highlighting of
+// lines and diffs), where the highlighted markup has to be split into lines
+// even though the spans in it may cover multiple lines (e.g. block comments).
+
+/// The kind of change a line represents in a diff.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffMarker {
+    Added,
+    Removed,
+    Unchanged,
+}
+
+impl DiffMarker {
+    pub fn class(self) -> &'static str {
+        match self {
+            DiffMarker::Added => "diff-add",
+            DiffMarker::Removed => "diff-remove",
+            DiffMarker::Unchanged => "diff-context",
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            DiffMarker::Added => "+",
+            DiffMarker::Removed => "-",
+            DiffMarker::Unchanged => " ",
+        }
+    }
+}
+
+/// Strip the `+`/`-` (or ` ` for unchanged lines) prefixes of the lines of
+/// a diff, returning the marker of each line along with the code without
+/// them such that it may be highlighted as the language it is in.  The
+/// `---`/`+++` header naming the files (i.e. a `---` line directly followed
+/// by a `+++` line) isn't code, so it is skipped.
+pub fn strip_diff_markers(code: &str) -> (Vec<DiffMarker>, String) {
+    let mut lines = code.split_inclusive('\n').peekable();
+    let mut markers = Vec::new();
+    let mut stripped = String::with_capacity(code.len());
+    while let Some(line) = lines.next() {
+        if line.starts_with("---")
+            && lines.peek().is_some_and(|next| next.starts_with("+++"))
+        {
+            lines.next();
+            continue;
+        }
+        let (marker, line) = match line.chars().next() {
+            Some('+') => (DiffMarker::Added, &line[1..]),
+            Some('-') => (DiffMarker::Removed, &line[1..]),
+            Some(' ') => (DiffMarker::Unchanged, &line[1..]),
+            _ => (DiffMarker::Unchanged, line),
+        };
+        markers.push(marker);
+        stripped.push_str(line);
+    }
+    (markers, stripped)
+}
+
+/// The number of lines in the code, as rendered (i.e. without counting the
+/// empty line after a trailing newline).
+pub fn line_count(code: &str) -> usize {
+    code.split_inclusive('\n').count()
+}
+
+/// Pad the code with empty lines up to `count` lines, for code shown next to
+/// some other code that only arrives later, such that the layout doesn't
+/// reflow once it does.
+pub fn pad_lines(code: &str, count: usize) -> String {
+    let padding = count.saturating_sub(line_count(code));
+    let newline = if code.is_empty() || code.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    format!("{code}{newline}{}", "\n".repeat(padding))
+}
+
+/// The markup shown under the `<textarea>` of an editor of the `code`: the
+/// `highlighted` markup (along with the code it was highlighted from), or
+/// the escaped code until it is highlighted.  The highlighting lags behind
+/// the edits, so the markup of some earlier code is stale and left out, as
+/// it would hide what was typed since.  As the textarea shows the empty line
+/// after a trailing newline, the markup needs something after it for the
+/// line to be shown as well.
+pub fn editor_markup(highlighted: Option<(&str, &str)>, code: &str) -> String {
+    let mut html = match highlighted {
+        Some((source, html)) if source == code => html.to_string(),
+        _ => html_escape::encode_text(code).into_owned(),
+    };
+    if code.ends_with('\n') {
+        html.push(' ');
+    }
+    html
+}
+
+/// Split the highlighted markup (as produced by `sanitize`, so consisting of
+/// nothing but text and `<span class="...">`) into exactly `count` lines,
+/// without the newlines.  The spans still open at the end of a line are
+/// closed and then opened again on the next line, such that every line is
+/// well-formed on its own.
+pub fn split_html_lines(html: &str, count: usize) -> Vec<String> {
+    let mut lines = Vec::with_capacity(count);
+    let mut line = String::new();
+    let mut open = Vec::<&str>::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        if rest.starts_with("</span>") {
+            open.pop();
+            line.push_str("</span>");
+            rest = &rest["</span>".len()..];
+        } else if rest.starts_with("<span") {
+            let len = rest.find('>').map_or(rest.len(), |n| n + 1);
+            open.push(&rest[..len]);
+            line.push_str(&rest[..len]);
+            rest = &rest[len..];
+        } else if let Some(after) = rest.strip_prefix('\n') {
+            line.extend(open.iter().map(|_| "</span>"));
+            lines.push(std::mem::take(&mut line));
+            line.extend(open.iter().copied());
+            rest = after;
+        } else {
+            let len = rest.find(['<', '\n']).unwrap_or(rest.len()).max(1);
+            line.push_str(&rest[..len]);
+            rest = &rest[len..];
+        }
+    }
+    if lines.len() < count {
+        lines.push(line);
+    }
+    lines.resize(count, String::new());
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_spans_across_lines() {
+        let html = "<span class=\"hljs-comment\">/* a\nb */</span> x\n\
+                    <span class=\"hljs-string\">\"c\"</span>\n";
+        assert_eq!(
+            split_html_lines(html, 3),
+            [
+                "<span class=\"hljs-comment\">/* a</span>",
+                "<span class=\"hljs-comment\">b */</span> x",
+                "<span class=\"hljs-string\">\"c\"</span>",
+            ]
+        );
+        assert_eq!(split_html_lines("a\nb", 3), ["a", "b", ""]);
+        assert_eq!(split_html_lines("a\nb\nc\n", 2), ["a", "b"]);
+    }
+
+    #[test]
+    fn pad_to_line_count() {
+        assert_eq!(pad_lines("a\nb\n", 4), "a\nb\n\n\n");
+        assert_eq!(pad_lines("a\nb", 3), "a\nb\n\n");
+        assert_eq!(line_count(&pad_lines("a\nb", 3)), 3);
+        assert_eq!(pad_lines("a\nb\nc\n", 2), "a\nb\nc\n");
+        assert_eq!(pad_lines("", 2), "\n\n");
+    }
+
+    #[test]
+    fn editor_markup_escaped_until_highlighted() {
+        assert_eq!(editor_markup(None, "a < b"), "a &lt; b");
+        assert_eq!(
+            editor_markup(Some(("a", "<span class=\"hljs-x\">a</span>")), "a"),
+            "<span class=\"hljs-x\">a</span>"
+        );
+        // the last, empty line is kept
+        assert_eq!(editor_markup(None, "a\n"), "a\n ");
+        assert_eq!(editor_markup(Some(("a\n", "a\n")), "a\n"), "a\n ");
+    }
+
+    #[test]
+    fn editor_markup_escaped_while_stale() {
+        // typed since the code was highlighted
+        let highlighted = Some(("a", "<span class=\"hljs-x\">a</span>"));
+        assert_eq!(editor_markup(highlighted, "a < b"), "a &lt; b");
+        assert_eq!(editor_markup(highlighted, "a\n"), "a\n ");
+    }
+
+    #[test]
+    fn diff_markers() {
+        let (markers, code) = strip_diff_markers(" a\n-b\n+c\nd");
+        assert_eq!(
+            markers,
+            [
+                DiffMarker::Unchanged,
+                DiffMarker::Removed,
+                DiffMarker::Added,
+                DiffMarker::Unchanged,
+            ]
+        );
+        assert_eq!(code, "a\nb\nc\nd");
+        assert_eq!(line_count(&code), 4);
+    }
+
+    #[test]
+    fn diff_headers_skipped() {
+        let (markers, code) = strip_diff_markers(
+            "--- a/src/main.rs\n+++ b/src/main.rs\n a\n--- b\n+c\n",
+        );
+        assert_eq!(
+            markers,
+            [
+                DiffMarker::Unchanged,
+                DiffMarker::Removed,
+                DiffMarker::Added,
+            ]
+        );
+        // the removed `-- b` isn't a header, as no `+++` line follows it
+        assert_eq!(code, "a\n-- b\nc\n");
+    }
+}
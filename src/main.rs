@@ -1,33 +1,17 @@
-#[cfg(feature = "ssr")]
-mod latency {
-    use std::sync::{Mutex, OnceLock};
-    pub static LATENCY: OnceLock<
-        Mutex<std::iter::Cycle<std::slice::Iter<'_, u64>>>,
-    > = OnceLock::new();
-    pub static ES_LATENCY: OnceLock<
-        Mutex<std::iter::Cycle<std::slice::Iter<'_, u64>>>,
-    > = OnceLock::new();
-}
-
 #[cfg(feature = "ssr")]
 #[tokio::main]
 async fn main() {
-    use axum::{
-        body::Body,
-        extract::Request,
-        http::{
-            header::{self, HeaderValue},
-            StatusCode,
-        },
-        middleware::{self, Next},
-        response::{IntoResponse, Response},
-        routing::get,
-        Router,
-    };
-    use axum_js_ssr::app::*;
-    use http_body_util::BodyExt;
+    use axum_js_ssr::{assets, latency, server, source, trace};
     use leptos::prelude::*;
-    use leptos_axum::{generate_route_list, LeptosRoutes};
+
+    let log_format = std::env::var("LOG_FORMAT")
+        .map(|format| format.parse().unwrap_or_else(|err| panic!("{err}")))
+        .unwrap_or_default();
+    trace::init(log_format);
+    let code_source = source::from_env().unwrap_or_else(|err| {
+        tracing::error!("{err}");
+        std::process::exit(1);
+    });
 
     latency::LATENCY.get_or_init(|| [0, 4, 40, 400].iter().cycle().into());
     latency::ES_LATENCY.get_or_init(|| [0].iter().cycle().into());
@@ -41,107 +25,39 @@ async fn main() {
     let conf = get_configuration(None).unwrap();
     let addr = conf.leptos_options.site_addr;
     let leptos_options = conf.leptos_options;
-    // Generate the list of routes in your Leptos App
-    let routes = generate_route_list(App);
-
-    async fn highlight_js() -> impl IntoResponse {
-        (
-            [(header::CONTENT_TYPE, "text/javascript")],
-            include_str!(
-                "../node_modules/@highlightjs/cdn-assets/highlight.min.js"
+    let pkg_dir = std::path::Path::new(leptos_options.site_root.as_ref())
+        .join(leptos_options.site_pkg_dir.as_ref());
+    // Compressing the unoptimized bundle produced for development takes far
+    // too long to be done on every restart by `cargo leptos watch`.
+    // This is waited upon (off the async workers) before serving, so that
+    // no partially written file is ever picked up by `ServeDir`.
+    if leptos_options.env == Env::PROD {
+        let dir = pkg_dir.clone();
+        match tokio::task::spawn_blocking(move || assets::precompress_dir(&dir))
+            .await
+        {
+            Ok(Ok(())) => (),
+            Ok(Err(err)) => tracing::error!(
+                "failed to precompress {}: {err}",
+                pkg_dir.display()
             ),
-        )
-    }
-
-    async fn latency_for_highlight_js(
-        req: Request,
-        next: Next,
-    ) -> Result<impl IntoResponse, (StatusCode, String)> {
-        let uri_parts = &mut req.uri().path().rsplit('/');
-
-        let is_highlightjs = uri_parts.next() == Some("highlight.min.js");
-        let es = uri_parts.next() == Some("es");
-        let module_type = if es { "es module " } else { "standard " };
-        let res = next.run(req).await;
-        if is_highlightjs {
-            // additional processing if the filename is the test subject
-            let (mut parts, body) = res.into_parts();
-            let bytes = body
-                .collect()
-                .await
-                .map_err(|err| {
-                    (
-                        StatusCode::BAD_REQUEST,
-                        format!("error reading body: {err}"),
-                    )
-                })?
-                .to_bytes();
-            let latency = if es {
-                &latency::ES_LATENCY
-            } else {
-                &latency::LATENCY
-            };
-
-            let delay = match latency
-                .get()
-                .expect("latency cycle wasn't set up")
-                .try_lock()
-            {
-                Ok(ref mut mutex) => {
-                    *mutex.next().expect("cycle always has next")
-                }
-                Err(_) => 0,
-            };
-
-            // inject the logging of the delay used into the target script
-            log!(
-                "loading {module_type}highlight.min.js with latency of \
-                 {delay} ms"
-            );
-            let js_log = format!(
-                "\nconsole.log('loaded {module_type}highlight.js with a \
-                 minimum latency of {delay} ms');"
-            );
-            tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
-
-            let bytes = [bytes, js_log.into()].concat();
-            let length = bytes.len();
-            let body = Body::from(bytes);
-
-            // Provide the bare minimum set of headers to avoid browser cache.
-            parts.headers = header::HeaderMap::from_iter(
-                [
-                    (
-                        header::CONTENT_TYPE,
-                        HeaderValue::from_static("text/javascript"),
-                    ),
-                    (header::CONTENT_LENGTH, HeaderValue::from(length)),
-                ]
-                .into_iter(),
-            );
-            Ok(Response::from_parts(parts, body))
-        } else {
-            Ok(res)
+            Err(err) => tracing::error!("precompression panicked: {err}"),
         }
     }
 
-    let app = Router::new()
-        .route("/highlight.min.js", get(highlight_js))
-        .leptos_routes(&leptos_options, routes, {
-            let leptos_options = leptos_options.clone();
-            move || shell(leptos_options.clone())
-        })
-        .fallback(leptos_axum::file_and_error_handler(shell))
-        .layer(middleware::from_fn(latency_for_highlight_js))
-        .with_state(leptos_options);
+    let app = server::router(leptos_options, code_source);
 
     // run our app with hyper
     // `axum::Server` is a re-export of `hyper::Server`
-    log!("listening on http://{}", &addr);
+    tracing::info!("listening on http://{}", &addr);
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    // the address of the client, for the telemetry to be rate limited by
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
 
 #[cfg(not(feature = "ssr"))]
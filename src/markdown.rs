@@ -0,0 +1,274 @@
+// Parsing of Markdown into a tree of nodes for the `Markdown` component to
+// render as Leptos views, such that documentation may be written in Markdown
+// rather than by hand in `view!`.  As the same parsing is done under SSR and
+// CSR on the same text, the rendered views are identical for hydration.
+//
+// The Markdown may come from anywhere, so raw HTML is dropped rather than
+// rendered and only links/images with safe URLs are kept.
+use pulldown_cmark::{
+    CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    Text(String),
+    /// Inline code.
+    Code(String),
+    SoftBreak,
+    HardBreak,
+    Rule,
+    /// A code block, with the language of its fence (if any).
+    CodeBlock {
+        lang: Option<String>,
+        code: String,
+    },
+    Element {
+        kind: Element,
+        children: Vec<Node>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Element {
+    Paragraph,
+    Heading(u8),
+    BlockQuote,
+    /// A list, numbered from the start if ordered.
+    List(Option<u64>),
+    Item,
+    Emphasis,
+    Strong,
+    Strikethrough,
+    Link {
+        href: Option<String>,
+        title: String,
+    },
+    /// An image, with its alt text as the children.
+    Image {
+        src: Option<String>,
+        title: String,
+    },
+    Table,
+    TableHead,
+    TableRow,
+    TableCell {
+        head: bool,
+    },
+}
+
+// The nodes being collected for the tag currently open.
+enum Frame {
+    Element(Element),
+    CodeBlock(Option<String>),
+    // the children are added to the parent as is
+    Transparent,
+    // the children are dropped
+    Drop,
+}
+
+/// Whether the URL is safe to be used as a link, i.e. it is either relative
+/// or uses one of the `http`, `https` and `mailto` schemes.
+pub fn safe_url(url: &str) -> bool {
+    match url.find([':', '/', '?', '#']) {
+        Some(n) if url[n..].starts_with(':') => {
+            let scheme = &url[..n];
+            ["http", "https", "mailto"]
+                .iter()
+                .any(|allowed| scheme.eq_ignore_ascii_case(allowed))
+        }
+        _ => true,
+    }
+}
+
+/// The plain text of the nodes, e.g. for the alt text of images.
+pub fn plain_text(nodes: &[Node]) -> String {
+    nodes
+        .iter()
+        .map(|node| match node {
+            Node::Text(text) | Node::Code(text) => text.clone(),
+            Node::SoftBreak | Node::HardBreak => " ".to_string(),
+            Node::Element { children, .. } => plain_text(children),
+            Node::Rule | Node::CodeBlock { .. } => String::new(),
+        })
+        .collect()
+}
+
+/// Parse the Markdown (CommonMark, along with tables and strikethrough).
+pub fn parse(text: &str) -> Vec<Node> {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let mut stack = vec![(Frame::Transparent, Vec::new())];
+    let mut in_table_head = false;
+    for event in Parser::new_ext(text, options) {
+        let node = match event {
+            Event::Start(tag) => {
+                let frame = match tag {
+                    Tag::Paragraph => Frame::Element(Element::Paragraph),
+                    Tag::Heading { level, .. } => {
+                        Frame::Element(Element::Heading(heading_level(level)))
+                    }
+                    Tag::BlockQuote(_) => Frame::Element(Element::BlockQuote),
+                    Tag::CodeBlock(CodeBlockKind::Fenced(info)) => {
+                        Frame::CodeBlock(
+                            info.split_whitespace().next().map(str::to_string),
+                        )
+                    }
+                    Tag::CodeBlock(CodeBlockKind::Indented) => {
+                        Frame::CodeBlock(None)
+                    }
+                    Tag::List(start) => Frame::Element(Element::List(start)),
+                    Tag::Item => Frame::Element(Element::Item),
+                    Tag::Emphasis => Frame::Element(Element::Emphasis),
+                    Tag::Strong => Frame::Element(Element::Strong),
+                    Tag::Strikethrough => {
+                        Frame::Element(Element::Strikethrough)
+                    }
+                    Tag::Link {
+                        dest_url, title, ..
+                    } => Frame::Element(Element::Link {
+                        href: safe_url(&dest_url).then(|| dest_url.to_string()),
+                        title: title.to_string(),
+                    }),
+                    Tag::Image {
+                        dest_url, title, ..
+                    } => Frame::Element(Element::Image {
+                        src: safe_url(&dest_url).then(|| dest_url.to_string()),
+                        title: title.to_string(),
+                    }),
+                    Tag::Table(_) => Frame::Element(Element::Table),
+                    Tag::TableHead => {
+                        in_table_head = true;
+                        Frame::Element(Element::TableHead)
+                    }
+                    Tag::TableRow => Frame::Element(Element::TableRow),
+                    Tag::TableCell => Frame::Element(Element::TableCell {
+                        head: in_table_head,
+                    }),
+                    Tag::HtmlBlock | Tag::MetadataBlock(_) => Frame::Drop,
+                    _ => Frame::Transparent,
+                };
+                stack.push((frame, Vec::new()));
+                continue;
+            }
+            Event::End(tag) => {
+                if tag == TagEnd::TableHead {
+                    in_table_head = false;
+                }
+                let (frame, children) =
+                    stack.pop().expect("events are balanced");
+                let parent = &mut stack.last_mut().expect("root remains").1;
+                match frame {
+                    Frame::Element(kind) => {
+                        parent.push(Node::Element { kind, children })
+                    }
+                    Frame::CodeBlock(lang) => parent.push(Node::CodeBlock {
+                        lang,
+                        code: plain_text(&children),
+                    }),
+                    Frame::Transparent => parent.extend(children),
+                    Frame::Drop => {}
+                }
+                continue;
+            }
+            Event::Text(text) => Node::Text(text.into_string()),
+            Event::Code(code) => Node::Code(code.into_string()),
+            Event::SoftBreak => Node::SoftBreak,
+            Event::HardBreak => Node::HardBreak,
+            Event::Rule => Node::Rule,
+            // raw HTML, along with the extensions that aren't enabled
+            _ => continue,
+        };
+        stack.last_mut().expect("root remains").1.push(node);
+    }
+    stack.pop().map(|(_, nodes)| nodes).unwrap_or_default()
+}
+
+fn heading_level(level: HeadingLevel) -> u8 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Node {
+        Node::Text(text.to_string())
+    }
+
+    fn element(kind: Element, children: Vec<Node>) -> Node {
+        Node::Element { kind, children }
+    }
+
+    #[test]
+    fn parse_blocks() {
+        let nodes = parse(
+            "# Title\n\n\
+             Some *text* with `code`.\n\n\
+             ```rust ignore\nfn main() {}\n```\n\n\
+             ```\nplain\n```\n\n\
+             1. one\n",
+        );
+        assert_eq!(
+            nodes,
+            [
+                element(Element::Heading(1), vec![text("Title")]),
+                element(
+                    Element::Paragraph,
+                    vec![
+                        text("Some "),
+                        element(Element::Emphasis, vec![text("text")]),
+                        text(" with "),
+                        Node::Code("code".to_string()),
+                        text("."),
+                    ]
+                ),
+                Node::CodeBlock {
+                    lang: Some("rust".to_string()),
+                    code: "fn main() {}\n".to_string(),
+                },
+                Node::CodeBlock {
+                    lang: None,
+                    code: "plain\n".to_string(),
+                },
+                element(
+                    Element::List(Some(1)),
+                    vec![element(Element::Item, vec![text("one")])]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn drop_unsafe_content() {
+        let nodes = parse(
+            "<script>alert(1)</script>\n\n[a](javascript:alert(1)) <b>b</b>",
+        );
+        assert_eq!(
+            nodes,
+            [element(
+                Element::Paragraph,
+                vec![
+                    element(
+                        Element::Link {
+                            href: None,
+                            title: String::new(),
+                        },
+                        vec![text("a")]
+                    ),
+                    text(" "),
+                    text("b"),
+                ]
+            )]
+        );
+        assert!(safe_url("/path:with-colon"));
+        assert!(safe_url("https://example.com"));
+        assert!(!safe_url("JavaScript:alert(1)"));
+        assert!(!safe_url("data:text/html,x"));
+    }
+}
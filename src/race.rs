@@ -0,0 +1,229 @@
+// Deterministic control over the events the examples race against each
+// other, for the race driver of the end-to-end suite (see `end2end/race`) to
+// have them happen in every order.  Each of the events is held back by a
+// gate until the driver releases it:
+//
+// - `hljs`: the arrival of the standard `highlight.min.js` (as served through
+//   `latency::latency_for_highlight_js`);
+// - `fetch_code`: the resolution of `fetch_code`/`fetch_codes`, whether called
+//   during SSR or by the client;
+// - `hydrate`: the arrival of the WASM bundle, before which hydration can't
+//   complete.
+//
+// The gates apply to the requests carrying the `race_run` cookie naming the
+// run, and are released through `POST /race/{run}/{gate}` (with the state
+// of the run dropped through `DELETE /race/{run}`, or once it has been left
+// alone for `GATE_TIMEOUT`).  As this allows any
+// client to hold requests open, none of this is enabled unless the server is
+// started with `RACE_CONTROL=1`.
+use axum::{
+    extract::{Path, Request},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+    routing::{delete, post},
+    Router,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::watch;
+
+/// The cookie naming the run the request is part of.
+pub const RACE_COOKIE: &str = "race_run";
+
+/// How long a gate may hold a request before letting it through anyway,
+/// such that an abandoned run doesn't hold requests forever.
+pub const GATE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Gate {
+    Hljs,
+    FetchCode,
+    Hydrate,
+}
+
+impl Gate {
+    pub const ALL: [Gate; 3] = [Gate::Hljs, Gate::FetchCode, Gate::Hydrate];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Gate::Hljs => "hljs",
+            Gate::FetchCode => "fetch_code",
+            Gate::Hydrate => "hydrate",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Gate::ALL.into_iter().find(|gate| gate.name() == name)
+    }
+}
+
+struct Run {
+    released: watch::Sender<HashSet<Gate>>,
+    touched: Instant,
+}
+
+// the gates released for each of the runs
+static RUNS: LazyLock<Mutex<HashMap<String, Run>>> =
+    LazyLock::new(Default::default);
+
+// The state of the `run`, created if need be, after evicting the runs left
+// alone for longer than any of their requests may be held (which lets the
+// requests still waiting on them through).  As any client may name a run,
+// this is what keeps the runs from piling up.
+fn touch<'a>(
+    runs: &'a mut HashMap<String, Run>,
+    run: &str,
+    now: Instant,
+) -> &'a mut Run {
+    runs.retain(|_, run| now.duration_since(run.touched) < GATE_TIMEOUT);
+    let run = runs.entry(run.to_string()).or_insert_with(|| Run {
+        released: watch::channel(HashSet::new()).0,
+        touched: now,
+    });
+    run.touched = now;
+    run
+}
+
+/// Whether the gates are enabled, i.e. the server was started with
+/// `RACE_CONTROL=1`.
+pub fn enabled() -> bool {
+    static ENABLED: LazyLock<bool> =
+        LazyLock::new(|| std::env::var("RACE_CONTROL").as_deref() == Ok("1"));
+    *ENABLED
+}
+
+// The run the request is part of, if any.
+fn run_of(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| {
+            pair.trim().strip_prefix(RACE_COOKIE)?.strip_prefix('=')
+        })
+        .filter(|run| !run.is_empty())
+}
+
+fn released(run: &str) -> watch::Receiver<HashSet<Gate>> {
+    let mut runs = RUNS.lock().expect("race runs poisoned");
+    touch(&mut runs, run, Instant::now()).released.subscribe()
+}
+
+/// Wait for the `gate` to be released for the run of the request with the
+/// `headers`, if the gates are enabled and the request is part of a run.
+pub async fn wait(headers: &HeaderMap, gate: Gate) {
+    if !enabled() {
+        return;
+    }
+    let Some(run) = run_of(headers) else {
+        return;
+    };
+    let mut released = released(run);
+    let _ = tokio::time::timeout(
+        GATE_TIMEOUT,
+        released.wait_for(|released| released.contains(&gate)),
+    )
+    .await;
+}
+
+/// Release the `gate` for the `run`, letting through the requests held by
+/// it, along with any made from then on.
+pub fn release(run: &str, gate: Gate) {
+    let mut runs = RUNS.lock().expect("race runs poisoned");
+    touch(&mut runs, run, Instant::now())
+        .released
+        .send_modify(|released| {
+            released.insert(gate);
+        });
+}
+
+/// Drop the state of the `run`, which also releases all its gates.
+pub fn forget(run: &str) {
+    let run = RUNS.lock().expect("race runs poisoned").remove(run);
+    if let Some(run) = run {
+        run.released
+            .send_modify(|released| released.extend(Gate::ALL));
+    }
+}
+
+/// The routes for the driver to release the gates of a run.
+pub fn router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new()
+        .route(
+            "/race/:run/:gate",
+            post(|Path((run, gate)): Path<(String, String)>| async move {
+                let Some(gate) = Gate::from_name(&gate) else {
+                    return StatusCode::NOT_FOUND;
+                };
+                release(&run, gate);
+                StatusCode::NO_CONTENT
+            }),
+        )
+        .route(
+            "/race/:run",
+            delete(|Path(run): Path<String>| async move {
+                forget(&run);
+                StatusCode::NO_CONTENT
+            }),
+        )
+}
+
+/// Hold back the WASM bundle (see `Gate::Hydrate`).
+pub async fn gate_wasm(req: Request, next: Next) -> Response {
+    if req.uri().path().ends_with(".wasm") {
+        wait(req.headers(), Gate::Hydrate).await;
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn gates_hold_until_released() {
+        let headers = HeaderMap::from_iter([(
+            header::COOKIE,
+            "a=b; race_run=test-run".parse().unwrap(),
+        )]);
+        assert_eq!(run_of(&headers), Some("test-run"));
+
+        let mut released = released("test-run");
+        let held = tokio::spawn(async move {
+            released
+                .wait_for(|released| released.contains(&Gate::FetchCode))
+                .await
+                .map(|_| ())
+        });
+        release("test-run", Gate::Hljs);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!held.is_finished());
+        release("test-run", Gate::FetchCode);
+        assert!(held.await.unwrap().is_ok());
+
+        forget("test-run");
+        assert!(!RUNS.lock().unwrap().contains_key("test-run"));
+        assert_eq!(Gate::from_name("fetch_code"), Some(Gate::FetchCode));
+        assert_eq!(Gate::from_name("bogus"), None);
+    }
+
+    #[tokio::test]
+    async fn runs_evicted_when_left_alone() {
+        let mut runs = HashMap::new();
+        let start = Instant::now();
+        let mut held = touch(&mut runs, "stale", start).released.subscribe();
+        touch(&mut runs, "active", start);
+        touch(&mut runs, "active", start + GATE_TIMEOUT / 2);
+        touch(&mut runs, "new", start + GATE_TIMEOUT);
+        let mut names = runs.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["active", "new"]);
+        // the requests held by the evicted run are let through
+        assert!(held.wait_for(|_| false).await.is_err());
+    }
+}
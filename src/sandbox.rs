@@ -0,0 +1,950 @@
+// Compilation and execution of the Rust snippets submitted to `run_code`,
+// using the toolchain installed on the server host.  The snippets are
+// arbitrary code from anyone able to reach the server, so running them is
+// only enabled with `RUN_CODE=1`, and every process (including the
+// compiler, as `include_str!` and the like read files at compile time) is
+// run in a jail of its own:
+//
+// - new user, mount, pid, network, IPC, UTS and cgroup namespaces, such that
+//   it has no network access, sees no other processes, and holds no
+//   capabilities outside of the namespaces (nor within, once exec'd);
+// - a throwaway tmpfs as its root, with nothing but read-only binds of the
+//   system directories (and of the toolchain for the compiler), the device
+//   nodes that are safe to use, and the directory of the snippet;
+// - resource limits through `setrlimit`, including the number of processes
+//   against fork bombs (root being exempt from it, a server run as root
+//   runs the jail as `nobody`), and a seccomp filter denying the system
+//   calls that could undo the jail or that mostly serve to attack the
+//   kernel;
+// - an environment with nothing of the server's in it.
+//
+// The process is run as the child of an init that is the first process of
+// the pid namespace, so killing the init (e.g. on timeout) takes down every
+// process left in the namespace, whichever session or process group they
+// moved to.
+//
+// This fails closed: should the namespaces be unavailable (e.g. where
+// unprivileged user namespaces are disabled, or outside of Linux), the
+// snippets are simply not run.  What it doesn't cover is the attack surface
+// of the kernel that remains through the allowed system calls, and the use
+// of the CPU and memory of the host beyond the limits of each process,
+// which is only bounded by running a few snippets at a time.
+use crate::api::RunOutput;
+use jail::Jail;
+use std::{
+    io,
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::LazyLock,
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::Command,
+    sync::{OnceCell, Semaphore},
+};
+
+/// Whether running code is enabled, i.e. the server was started with
+/// `RUN_CODE=1`.
+pub fn enabled() -> bool {
+    static ENABLED: LazyLock<bool> =
+        LazyLock::new(|| std::env::var("RUN_CODE").as_deref() == Ok("1"));
+    *ENABLED
+}
+
+#[derive(Debug, Error)]
+pub enum SandboxError {
+    #[error("unable to locate the toolchain: {0}")]
+    Toolchain(String),
+    // this includes the failures to confine the process
+    #[error("unable to start the sandboxed process: {0}")]
+    Spawn(io::Error),
+    #[error("sandbox io error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// The limits applied to a sandboxed process.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// The wall clock time before the process group is killed.
+    pub timeout: Duration,
+    /// The CPU time (`RLIMIT_CPU`), in seconds.
+    pub cpu_secs: u64,
+    /// The address space (`RLIMIT_AS`), in bytes.
+    pub memory: u64,
+    /// The size of any file written (`RLIMIT_FSIZE`), in bytes.
+    pub file_size: u64,
+    /// The processes (and threads) within the jail (`RLIMIT_NPROC`).
+    pub processes: u64,
+    /// The bytes kept of each of stdout and stderr.
+    pub output: usize,
+}
+
+/// The limits for compiling a snippet with `rustc`.
+pub const COMPILE_LIMITS: Limits = Limits {
+    timeout: Duration::from_secs(30),
+    cpu_secs: 30,
+    memory: 2048 * 1024 * 1024,
+    file_size: 64 * 1024 * 1024,
+    processes: 128,
+    output: 64 * 1024,
+};
+
+/// The limits for running a compiled snippet.
+pub const RUN_LIMITS: Limits = Limits {
+    timeout: Duration::from_secs(5),
+    cpu_secs: 5,
+    memory: 256 * 1024 * 1024,
+    file_size: 1024 * 1024,
+    processes: 16,
+    output: 64 * 1024,
+};
+
+// the number of snippets compiled or run at the same time
+static SLOTS: Semaphore = Semaphore::const_new(2);
+
+// Where the directory of the snippet and the toolchain are within the jail.
+const JAIL_WORK: &str = "/work";
+const JAIL_SYSROOT: &str = "/sysroot";
+
+struct Toolchain {
+    sysroot: PathBuf,
+    linker: PathBuf,
+}
+
+// Locate the toolchain once, through `RUSTC` (or the `rustc` on the `PATH`)
+// and the `cc` on the `PATH`, with the environment of the server as a
+// rustup proxy relies on it.  Only the toolchain itself is run in the jail.
+async fn toolchain() -> Result<&'static Toolchain, SandboxError> {
+    static TOOLCHAIN: OnceCell<Toolchain> = OnceCell::const_new();
+    TOOLCHAIN
+        .get_or_try_init(|| async {
+            let rustc =
+                std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+            let output = Command::new(rustc)
+                .args(["--print", "sysroot"])
+                .output()
+                .await
+                .map_err(|err| SandboxError::Toolchain(err.to_string()))?;
+            if !output.status.success() {
+                return Err(SandboxError::Toolchain(
+                    String::from_utf8_lossy(&output.stderr).into_owned(),
+                ));
+            }
+            let sysroot = String::from_utf8_lossy(&output.stdout);
+            let linker = std::env::var_os("PATH")
+                .and_then(|path| {
+                    std::env::split_paths(&path)
+                        .find_map(|dir| dir.join("cc").canonicalize().ok())
+                })
+                .ok_or_else(|| {
+                    SandboxError::Toolchain("no `cc` to link with".into())
+                })?;
+            Ok(Toolchain {
+                sysroot: sysroot.trim().into(),
+                linker,
+            })
+        })
+        .await
+}
+
+/// Compile and run the Rust `code` as a binary crate, returning the output
+/// of the compiler instead if it fails to compile.  The `rustc` used may be
+/// set through the `RUSTC` environment variable.
+pub async fn run(code: &str) -> Result<RunOutput, SandboxError> {
+    let toolchain = toolchain().await?;
+    let _slot = SLOTS.acquire().await.expect("semaphore is never closed");
+    let dir = tempfile::Builder::new().prefix("run_code").tempdir()?;
+    let (work, root) = (dir.path().join("work"), dir.path().join("root"));
+    tokio::fs::create_dir(&work).await?;
+    tokio::fs::create_dir(&root).await?;
+    tokio::fs::write(work.join("main.rs"), code).await?;
+
+    // run from the toolchain itself rather than through a proxy
+    let mut compile =
+        Command::new(Path::new(JAIL_SYSROOT).join("bin").join("rustc"));
+    compile
+        .env_clear()
+        .env("PATH", "/usr/bin:/bin")
+        // where a rustup proxy would find the libraries of the toolchain
+        .env("LD_LIBRARY_PATH", Path::new(JAIL_SYSROOT).join("lib"))
+        .args([
+            "--edition=2021",
+            "--crate-type=bin",
+            "--crate-name=main",
+            "--sysroot",
+            JAIL_SYSROOT,
+            "-Cdebuginfo=0",
+        ])
+        .arg(format!("-Clinker={}", toolchain.linker.display()))
+        .args(["-o", "main", "main.rs"]);
+    let jail = Jail::new(&root, &work, Some(&toolchain.sysroot))
+        .map_err(SandboxError::Spawn)?;
+    let compiled = execute(compile, jail, COMPILE_LIMITS).await?;
+    if compiled.status != Some(0) {
+        return Ok(compiled);
+    }
+
+    let mut run = Command::new(Path::new(JAIL_WORK).join("main"));
+    run.env_clear();
+    let jail = Jail::new(&root, &work, None).map_err(SandboxError::Spawn)?;
+    Ok(RunOutput {
+        compiled: true,
+        ..execute(run, jail, RUN_LIMITS).await?
+    })
+}
+
+async fn execute(
+    mut command: Command,
+    jail: Jail,
+    limits: Limits,
+) -> Result<RunOutput, SandboxError> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    // SAFETY: only async-signal-safe calls are made between fork and exec.
+    unsafe {
+        command.pre_exec(move || jail.enter(&limits));
+    }
+    let mut child = command.spawn().map_err(SandboxError::Spawn)?;
+    let pgid = child.id().map(|id| id as libc::pid_t);
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+
+    let outcome = tokio::time::timeout(limits.timeout, async {
+        let (stdout, stderr) = tokio::try_join!(
+            read_limited(stdout, limits.output),
+            read_limited(stderr, limits.output),
+        )?;
+        Ok::<_, io::Error>((stdout, stderr, child.wait().await?))
+    })
+    .await;
+    // the process group includes the init of the jail, which takes down the
+    // rest of the processes in its pid namespace with it
+    if let Some(pgid) = pgid {
+        unsafe {
+            libc::killpg(pgid, libc::SIGKILL);
+        }
+    }
+    let Ok(result) = outcome else {
+        let _ = child.wait().await;
+        return Ok(RunOutput {
+            timed_out: true,
+            ..Default::default()
+        });
+    };
+    let (stdout, stderr, status) = result?;
+    Ok(RunOutput {
+        compiled: false,
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        status: status.code(),
+        signal: status.signal(),
+        timed_out: false,
+    })
+}
+
+#[cfg(target_os = "linux")]
+mod jail {
+    use super::{Limits, JAIL_SYSROOT, JAIL_WORK};
+    use libc::{c_int, c_ulong, sock_filter};
+    use std::{
+        ffi::{CStr, CString},
+        io,
+        os::unix::ffi::OsStrExt,
+        path::Path,
+        ptr,
+    };
+
+    // The filesystem of the jail, set up in order after the fork (where
+    // nothing may be allocated, so everything is prepared beforehand).
+    enum Step {
+        Dir(CString),
+        File(CString),
+        Symlink {
+            target: CString,
+            link: CString,
+        },
+        Bind {
+            source: CString,
+            target: CString,
+            // the flags to remount the bind with, if any
+            remount: Option<c_ulong>,
+        },
+    }
+
+    pub struct Jail {
+        // the empty directory the tmpfs root is mounted on
+        root: CString,
+        steps: Vec<Step>,
+        // the user to switch to before entering the jail, if any
+        user: Option<libc::uid_t>,
+        uid_map: CString,
+        gid_map: CString,
+        filter: Vec<sock_filter>,
+    }
+
+    // The user the jail is run as when the server runs as root, which is
+    // exempt from `RLIMIT_NPROC`.
+    const NOBODY: libc::uid_t = 65534;
+
+    const READ_ONLY: c_ulong =
+        libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV;
+
+    fn cstring(path: impl AsRef<Path>) -> io::Result<CString> {
+        CString::new(path.as_ref().as_os_str().as_bytes())
+            .map_err(|_| io::ErrorKind::InvalidInput.into())
+    }
+
+    impl Jail {
+        /// A jail rooted at the (empty) directory `root`, with `work` as
+        /// its working directory, along with the toolchain at `sysroot` for
+        /// compiling.
+        pub fn new(
+            root: &Path,
+            work: &Path,
+            sysroot: Option<&Path>,
+        ) -> io::Result<Self> {
+            let within = |path: &str| root.join(path.trim_start_matches('/'));
+            let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+            let user = (uid == 0).then_some(NOBODY);
+            if let Some(user) = user {
+                // the snippet and the binary are written by the jail
+                for entry in std::fs::read_dir(work)? {
+                    std::os::unix::fs::chown(entry?.path(), Some(user), None)?;
+                }
+                std::os::unix::fs::chown(work, Some(user), None)?;
+            }
+            let mut jail = Jail {
+                root: cstring(root)?,
+                steps: Vec::new(),
+                user,
+                uid_map: CString::new(format!("0 {} 1", user.unwrap_or(uid)))?,
+                gid_map: CString::new(format!("0 {} 1", user.unwrap_or(gid)))?,
+                filter: seccomp_filter()?,
+            };
+            // the system directories, which may be symlinks into `/usr`
+            for dir in ["/usr", "/bin", "/lib", "/lib64"] {
+                match std::fs::symlink_metadata(dir) {
+                    Ok(meta) if meta.is_symlink() => {
+                        jail.steps.push(Step::Symlink {
+                            target: cstring(std::fs::read_link(dir)?)?,
+                            link: cstring(within(dir))?,
+                        })
+                    }
+                    Ok(meta) if meta.is_dir() => jail.bind(
+                        Path::new(dir),
+                        &within(dir),
+                        Some(READ_ONLY),
+                    )?,
+                    _ => {}
+                }
+            }
+            jail.steps.push(Step::Dir(cstring(within("/dev"))?));
+            for dev in ["/dev/null", "/dev/zero", "/dev/urandom"] {
+                jail.bind(Path::new(dev), &within(dev), None)?;
+            }
+            jail.steps.push(Step::Dir(cstring(within("/tmp"))?));
+            jail.bind(
+                work,
+                &within(JAIL_WORK),
+                Some(libc::MS_NOSUID | libc::MS_NODEV),
+            )?;
+            if let Some(sysroot) = sysroot {
+                jail.bind(sysroot, &within(JAIL_SYSROOT), Some(READ_ONLY))?;
+            }
+            Ok(jail)
+        }
+
+        // Bind `source` (a directory, or else a file) on `target`, then
+        // remount it with the `flags` if any.
+        fn bind(
+            &mut self,
+            source: &Path,
+            target: &Path,
+            flags: Option<c_ulong>,
+        ) -> io::Result<()> {
+            let path = cstring(source)?;
+            // the flags of the mount of the source that may not be cleared
+            // from within the user namespace
+            let mut stat = unsafe { std::mem::zeroed::<libc::statvfs>() };
+            check(unsafe { libc::statvfs(path.as_ptr(), &mut stat) })?;
+            let locked = [
+                (libc::ST_RDONLY, libc::MS_RDONLY),
+                (libc::ST_NOEXEC, libc::MS_NOEXEC),
+            ]
+            .into_iter()
+            .filter(|(st, _)| stat.f_flag & st != 0)
+            .fold(0, |locked, (_, ms)| locked | ms);
+            self.steps.push(match source.is_dir() {
+                true => Step::Dir(cstring(target)?),
+                false => Step::File(cstring(target)?),
+            });
+            self.steps.push(Step::Bind {
+                source: path,
+                target: cstring(target)?,
+                remount: flags.map(|flags| flags | locked),
+            });
+            Ok(())
+        }
+
+        /// Enter the jail, in the child between the fork and exec of the
+        /// process, such that the process is exec'd in the jail as the
+        /// child of its init, while this process is left to wait and exit
+        /// as the process does.  This must not allocate.
+        pub fn enter(&self, limits: &Limits) -> io::Result<()> {
+            unsafe {
+                check(libc::setsid())?;
+                check(libc::setrlimit(libc::RLIMIT_CORE, &rlimit(0)))?;
+                // kept to write the maps of the user namespace, once there
+                // is no `/proc` left
+                let proc = check(libc::open(
+                    c"/proc/self".as_ptr(),
+                    libc::O_DIRECTORY | libc::O_CLOEXEC,
+                ))?;
+                // as root, the jail is set up before dropping to the user,
+                // such that its mounts are out of reach of the jail
+                if self.user.is_none() {
+                    self.enter_user_namespace(proc)?;
+                }
+                check(libc::unshare(libc::CLONE_NEWNS))?;
+                self.mount()?;
+                if let Some(user) = self.user {
+                    check(libc::setgroups(0, ptr::null()))?;
+                    check(libc::setgid(user))?;
+                    check(libc::setuid(user))?;
+                    // which is cleared along with the user, while the maps
+                    // of the namespace may only be written when dumpable
+                    check(libc::prctl(libc::PR_SET_DUMPABLE, 1 as c_ulong))?;
+                    self.enter_user_namespace(proc)?;
+                }
+                check(libc::unshare(
+                    libc::CLONE_NEWPID
+                        | libc::CLONE_NEWNET
+                        | libc::CLONE_NEWIPC
+                        | libc::CLONE_NEWUTS
+                        | libc::CLONE_NEWCGROUP,
+                ))?;
+
+                let mut status = [0; 2];
+                check(libc::pipe2(status.as_mut_ptr(), libc::O_CLOEXEC))?;
+                match check(libc::fork())? {
+                    0 => {}
+                    init => relay(init, status[0]),
+                }
+                // the init, i.e. the first process of the pid namespace,
+                // which is to die along with this process
+                libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL as c_ulong);
+                match libc::fork() {
+                    -1 => libc::_exit(SETUP_FAILED),
+                    0 => {}
+                    process => init(process, status[1]),
+                }
+                self.restrict(limits)
+            }
+        }
+
+        // Enter a user namespace where the user is root, with `proc` the
+        // directory of this process in `/proc`.
+        unsafe fn enter_user_namespace(&self, proc: c_int) -> io::Result<()> {
+            check(libc::unshare(libc::CLONE_NEWUSER))?;
+            write_file(proc, c"setgroups", b"deny")?;
+            write_file(proc, c"uid_map", self.uid_map.as_bytes())?;
+            write_file(proc, c"gid_map", self.gid_map.as_bytes())?;
+            Ok(())
+        }
+
+        unsafe fn mount(&self) -> io::Result<()> {
+            check(libc::mount(
+                ptr::null(),
+                c"/".as_ptr(),
+                ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                ptr::null(),
+            ))?;
+            check(libc::mount(
+                c"tmpfs".as_ptr(),
+                self.root.as_ptr(),
+                c"tmpfs".as_ptr(),
+                libc::MS_NOSUID | libc::MS_NODEV,
+                c"size=64m,mode=0755".as_ptr().cast(),
+            ))?;
+            for step in &self.steps {
+                match step {
+                    Step::Dir(path) => {
+                        check(libc::mkdir(path.as_ptr(), 0o755))?;
+                    }
+                    Step::File(path) => {
+                        check(libc::close(libc::open(
+                            path.as_ptr(),
+                            libc::O_CREAT | libc::O_WRONLY | libc::O_CLOEXEC,
+                            0o644,
+                        )))?;
+                    }
+                    Step::Symlink { target, link } => {
+                        check(libc::symlink(target.as_ptr(), link.as_ptr()))?;
+                    }
+                    Step::Bind {
+                        source,
+                        target,
+                        remount,
+                    } => {
+                        check(libc::mount(
+                            source.as_ptr(),
+                            target.as_ptr(),
+                            ptr::null(),
+                            libc::MS_BIND | libc::MS_REC,
+                            ptr::null(),
+                        ))?;
+                        if let Some(flags) = remount {
+                            check(libc::mount(
+                                ptr::null(),
+                                target.as_ptr(),
+                                ptr::null(),
+                                libc::MS_BIND | libc::MS_REMOUNT | flags,
+                                ptr::null(),
+                            ))?;
+                        }
+                    }
+                }
+            }
+            // swap the root for the tmpfs, with the old root detached
+            check(libc::chdir(self.root.as_ptr()))?;
+            check(libc::syscall(
+                libc::SYS_pivot_root,
+                c".".as_ptr(),
+                c".".as_ptr(),
+            ) as c_int)?;
+            check(libc::umount2(c".".as_ptr(), libc::MNT_DETACH))?;
+            check(libc::chdir(c"/work".as_ptr()))?;
+            Ok(())
+        }
+
+        // Applied to the process itself, right before it is exec'd.
+        unsafe fn restrict(&self, limits: &Limits) -> io::Result<()> {
+            check(libc::setrlimit(libc::RLIMIT_CPU, &rlimit(limits.cpu_secs)))?;
+            check(libc::setrlimit(libc::RLIMIT_AS, &rlimit(limits.memory)))?;
+            check(libc::setrlimit(
+                libc::RLIMIT_FSIZE,
+                &rlimit(limits.file_size),
+            ))?;
+            check(libc::setrlimit(libc::RLIMIT_NOFILE, &rlimit(64)))?;
+            // counted per user namespace, i.e. for the jail alone
+            check(libc::setrlimit(
+                libc::RLIMIT_NPROC,
+                &rlimit(limits.processes),
+            ))?;
+            // as the root of the user namespace, the process would regain
+            // its capabilities on exec unless the bounding set is emptied
+            for cap in 0..64 {
+                if libc::prctl(libc::PR_CAPBSET_DROP, cap as c_ulong) == -1
+                    && errno() != libc::EINVAL
+                {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            check(libc::prctl(
+                libc::PR_SET_NO_NEW_PRIVS,
+                1 as c_ulong,
+                0,
+                0,
+                0,
+            ))?;
+            let filter = libc::sock_fprog {
+                len: self.filter.len() as u16,
+                filter: self.filter.as_ptr().cast_mut(),
+            };
+            check(libc::prctl(
+                libc::PR_SET_SECCOMP,
+                libc::SECCOMP_MODE_FILTER as c_ulong,
+                &filter as *const libc::sock_fprog,
+            ))?;
+            let header = CapHeader {
+                version: CAPABILITY_VERSION_3,
+                pid: 0,
+            };
+            let data = [CapData::default(); 2];
+            check(libc::syscall(libc::SYS_capset, &header, data.as_ptr())
+                as c_int)?;
+            Ok(())
+        }
+    }
+
+    // The exit code of the processes outside of the jail when it couldn't be
+    // set up after the fork, where errors can no longer be returned.
+    const SETUP_FAILED: c_int = 125;
+
+    // Wait for the `init` of the jail, then exit as the process did (as
+    // reported by the init through `status_fd`).
+    unsafe fn relay(init: libc::pid_t, status_fd: c_int) -> ! {
+        keep_only(status_fd);
+        let mut status = 0;
+        while libc::waitpid(init, &mut status, 0) == -1 {
+            if errno() != libc::EINTR {
+                libc::_exit(SETUP_FAILED);
+            }
+        }
+        let mut reported = [0u8; 4];
+        if libc::read(3, reported.as_mut_ptr().cast(), reported.len()) == 4 {
+            status = c_int::from_ne_bytes(reported);
+        }
+        if libc::WIFSIGNALED(status) {
+            let signal = libc::WTERMSIG(status);
+            libc::signal(signal, libc::SIG_DFL);
+            libc::kill(libc::getpid(), signal);
+            libc::_exit(128 + signal);
+        }
+        libc::_exit(libc::WEXITSTATUS(status))
+    }
+
+    // Reap the processes of the jail until the `process` exits, then report
+    // its status through `status_fd` and exit, which kills whatever is left
+    // in the pid namespace.
+    unsafe fn init(process: libc::pid_t, status_fd: c_int) -> ! {
+        keep_only(status_fd);
+        let mut status = 0;
+        loop {
+            match libc::wait(&mut status) {
+                pid if pid == process => {
+                    let status = status.to_ne_bytes();
+                    libc::write(3, status.as_ptr().cast(), status.len());
+                    libc::_exit(0);
+                }
+                -1 if errno() != libc::EINTR => libc::_exit(SETUP_FAILED),
+                _ => {}
+            }
+        }
+    }
+
+    // Close the files other than stdio and `fd` (moved to 3), which include
+    // the pipe used to report the failure to exec the process: that is only
+    // closed once every process holding it exits or execs.
+    unsafe fn keep_only(fd: c_int) {
+        libc::dup2(fd, 3);
+        libc::syscall(
+            libc::SYS_close_range,
+            4 as libc::c_uint,
+            libc::c_uint::MAX,
+            0,
+        );
+    }
+
+    unsafe fn write_file(
+        dir: c_int,
+        path: &CStr,
+        contents: &[u8],
+    ) -> io::Result<()> {
+        let fd =
+            libc::openat(dir, path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        check(fd)?;
+        let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+        libc::close(fd);
+        if written != contents.len() as isize {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn check(ret: c_int) -> io::Result<c_int> {
+        if ret == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(ret)
+        }
+    }
+
+    fn errno() -> c_int {
+        io::Error::last_os_error().raw_os_error().unwrap_or(0)
+    }
+
+    fn rlimit(value: u64) -> libc::rlimit {
+        libc::rlimit {
+            rlim_cur: value as libc::rlim_t,
+            rlim_max: value as libc::rlim_t,
+        }
+    }
+
+    const CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+    #[repr(C)]
+    struct CapHeader {
+        version: u32,
+        pid: c_int,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct CapData {
+        effective: u32,
+        permitted: u32,
+        inheritable: u32,
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: Option<u32> = Some(0xc000_003e);
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: Option<u32> = Some(0xc000_00b7);
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    const AUDIT_ARCH: Option<u32> = None;
+
+    // The system calls denied to the jail: those to trace or access other
+    // processes, to mount or switch namespaces (which could undo the jail),
+    // and the interfaces of no use to a snippet that are a common source of
+    // kernel exploits.
+    const DENIED: &[libc::c_long] = &[
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_pivot_root,
+        libc::SYS_chroot,
+        libc::SYS_unshare,
+        libc::SYS_setns,
+        libc::SYS_open_tree,
+        libc::SYS_move_mount,
+        libc::SYS_fsopen,
+        libc::SYS_fsconfig,
+        libc::SYS_fsmount,
+        libc::SYS_fspick,
+        libc::SYS_mount_setattr,
+        libc::SYS_name_to_handle_at,
+        libc::SYS_open_by_handle_at,
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+        libc::SYS_bpf,
+        libc::SYS_perf_event_open,
+        libc::SYS_userfaultfd,
+        libc::SYS_io_uring_setup,
+        libc::SYS_io_uring_enter,
+        libc::SYS_io_uring_register,
+        libc::SYS_fanotify_init,
+        libc::SYS_kexec_load,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_reboot,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_syslog,
+        libc::SYS_acct,
+        libc::SYS_quotactl,
+    ];
+
+    const NAMESPACES: c_int = libc::CLONE_NEWUSER
+        | libc::CLONE_NEWNS
+        | libc::CLONE_NEWPID
+        | libc::CLONE_NEWNET
+        | libc::CLONE_NEWIPC
+        | libc::CLONE_NEWUTS
+        | libc::CLONE_NEWCGROUP;
+
+    fn seccomp_filter() -> io::Result<Vec<sock_filter>> {
+        use libc::{
+            BPF_ABS, BPF_JEQ, BPF_JGE, BPF_JMP, BPF_JSET, BPF_K, BPF_LD,
+            BPF_RET, BPF_W, SECCOMP_RET_ALLOW, SECCOMP_RET_ERRNO,
+            SECCOMP_RET_KILL_PROCESS,
+        };
+        use std::mem::offset_of;
+
+        let arch = AUDIT_ARCH.ok_or(io::ErrorKind::Unsupported)?;
+        let load = |offset: usize| sock_filter {
+            code: (BPF_LD | BPF_W | BPF_ABS) as u16,
+            jt: 0,
+            jf: 0,
+            k: offset as u32,
+        };
+        let jump = |op: u32, k: u32, jt: u8, jf: u8| sock_filter {
+            code: (BPF_JMP | op | BPF_K) as u16,
+            jt,
+            jf,
+            k,
+        };
+        let ret = |action: u32| sock_filter {
+            code: (BPF_RET | BPF_K) as u16,
+            jt: 0,
+            jf: 0,
+            k: action,
+        };
+        let errno = |errno: c_int| ret(SECCOMP_RET_ERRNO | errno as u32);
+
+        let mut filter = vec![
+            load(offset_of!(libc::seccomp_data, arch)),
+            jump(BPF_JEQ, arch, 1, 0),
+            ret(SECCOMP_RET_KILL_PROCESS),
+            load(offset_of!(libc::seccomp_data, nr)),
+        ];
+        // the x32 system calls, which are numbered from 0x40000000
+        if cfg!(target_arch = "x86_64") {
+            filter.extend([
+                jump(BPF_JGE, 0x4000_0000, 0, 1),
+                ret(SECCOMP_RET_KILL_PROCESS),
+            ]);
+        }
+        for &syscall in DENIED {
+            filter.extend([
+                jump(BPF_JEQ, syscall as u32, 0, 1),
+                errno(libc::EPERM),
+            ]);
+        }
+        // the flags of clone3 are behind a pointer, out of reach of the
+        // filter, so it is made out to be unsupported for the C library to
+        // fall back to clone, which is allowed without the namespace flags
+        filter.extend([
+            jump(BPF_JEQ, libc::SYS_clone3 as u32, 0, 1),
+            errno(libc::ENOSYS),
+            jump(BPF_JEQ, libc::SYS_clone as u32, 0, 3),
+            // the lower half of the flags, on the little endian targets
+            load(offset_of!(libc::seccomp_data, args)),
+            jump(BPF_JSET, NAMESPACES as u32, 0, 1),
+            errno(libc::EPERM),
+            ret(SECCOMP_RET_ALLOW),
+        ]);
+        Ok(filter)
+    }
+}
+
+// there are no namespaces elsewhere, so nothing may be run
+#[cfg(not(target_os = "linux"))]
+mod jail {
+    use super::Limits;
+    use std::{io, path::Path};
+
+    pub struct Jail;
+
+    impl Jail {
+        pub fn new(
+            _root: &Path,
+            _work: &Path,
+            _sysroot: Option<&Path>,
+        ) -> io::Result<Self> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+
+        pub fn enter(&self, _limits: &Limits) -> io::Result<()> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+}
+
+// Read up to `limit` bytes, after which the rest is discarded such that the
+// process doesn't block on writing it.
+async fn read_limited(
+    mut pipe: impl AsyncRead + Unpin,
+    limit: usize,
+) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    (&mut pipe).take(limit as u64).read_to_end(&mut buf).await?;
+    tokio::io::copy(&mut pipe, &mut tokio::io::sink()).await?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    // These need a toolchain on the host and the namespaces the jail is made
+    // of, so they are only run on request, with
+    // `cargo test --features ssr -- --ignored`.
+
+    #[tokio::test]
+    #[ignore = "needs a Rust toolchain and unprivileged user namespaces"]
+    async fn runs_code() {
+        let output =
+            run("fn main() { println!(\"hi\"); std::process::exit(3) }")
+                .await
+                .unwrap();
+        assert!(output.compiled);
+        assert_eq!(output.stdout, "hi\n");
+        assert_eq!(output.status, Some(3));
+
+        let output = run("fn main() { let x: u8 = \"\"; }").await.unwrap();
+        assert!(!output.compiled);
+        assert!(output.stderr.contains("mismatched types"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Rust toolchain and unprivileged user namespaces"]
+    async fn confines_code() {
+        let output = run(
+            "fn main() { \
+                 println!(\"{}\", std::net::TcpStream::connect(\"1.1.1.1:80\").is_err()); \
+                 println!(\"{}\", std::fs::metadata(\"/etc/passwd\").is_err()); \
+                 println!(\"{}\", std::fs::write(\"/usr/escape\", \"\").is_err()); \
+                 println!(\"{}\", std::process::id()); \
+             }",
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.stdout, "true\ntrue\ntrue\n2\n");
+
+        std::env::set_var("SANDBOX_TEST_SECRET", "leaked");
+        let output = run(
+            "fn main() { \
+                 println!(\"{:?}\", option_env!(\"SANDBOX_TEST_SECRET\")); \
+                 println!(\"{:?}\", std::env::var(\"SANDBOX_TEST_SECRET\").ok()); \
+             }",
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.stdout, "None\nNone\n");
+
+        let output =
+            run("fn main() { print!(include_str!(\"/etc/hostname\")) }")
+                .await
+                .unwrap();
+        assert!(!output.compiled);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Rust toolchain and unprivileged user namespaces"]
+    async fn contains_processes() {
+        // a fork bomb runs out of processes, and its processes are killed
+        // once the program exits
+        let start = Instant::now();
+        let output = run(
+            "fn main() { \
+                 if std::env::args().len() > 1 { \
+                     std::thread::sleep(std::time::Duration::from_secs(60)); \
+                 } \
+                 let spawned = (0..100) \
+                     .filter(|_| std::process::Command::new(\"/work/main\").arg(\"child\").spawn().is_ok()) \
+                     .count(); \
+                 println!(\"{spawned}\"); \
+             }",
+        )
+        .await
+        .unwrap();
+        assert!(!output.timed_out);
+        let spawned: u64 = output.stdout.trim().parse().unwrap();
+        assert!(spawned < RUN_LIMITS.processes);
+        assert!(start.elapsed() < RUN_LIMITS.timeout);
+
+        // neither are processes moved out of the process group left behind
+        let start = Instant::now();
+        let output = run(
+            "use std::os::unix::process::CommandExt; \
+             fn main() { \
+                 if std::env::args().len() > 1 { \
+                     std::thread::sleep(std::time::Duration::from_secs(60)); \
+                 } \
+                 std::process::Command::new(\"/work/main\").arg(\"child\").process_group(0).spawn().unwrap(); \
+                 println!(\"spawned\"); \
+             }",
+        )
+        .await
+        .unwrap();
+        assert!(!output.timed_out);
+        assert_eq!(output.stdout, "spawned\n");
+        assert!(start.elapsed() < RUN_LIMITS.timeout);
+
+        let output = run("fn main() { loop {} }").await.unwrap();
+        assert!(output.timed_out || output.signal == Some(libc::SIGXCPU));
+    }
+}
@@ -0,0 +1,341 @@
+// Sanitization of the markup produced by the highlighters before it is used
+// as `inner_html`.  The code itself comes from user submissions, and while
+// highlight.js escapes it by default, that relies on the options it is used
+// with; rather than trusting that, only the markup highlighters legitimately
+// produce is kept: `<span>` elements with `hljs-*` classes and text.
+//
+// Anything else is dropped (tags) or escaped (text), with the text entities
+// decoded first such that already escaped text is not escaped twice.  The
+// spans are always balanced in the output.
+
+/// The classes among those of a span that are produced by highlight.js,
+/// i.e. the `hljs-*` scopes and the sub-scopes with trailing underscores
+/// that follow them (e.g. the `function_` in `class="hljs-title function_"`
+/// or the `class_ inherited__` in `class="hljs-title class_ inherited__"`).
+/// A sub-scope not following a kept scope (e.g. a bare `function_`) is
+/// dropped.
+fn allowed_classes(classes: &str) -> Vec<&str> {
+    let scope = |class: &str| {
+        class.strip_prefix("hljs-").is_some_and(|scope| {
+            !scope.is_empty()
+                && scope.bytes().all(|b| {
+                    b.is_ascii_alphanumeric() || b == b'_' || b == b'-'
+                })
+        })
+    };
+    let sub_scope = |class: &str| {
+        class.ends_with('_')
+            && class.bytes().all(|b| b.is_ascii_lowercase() || b == b'_')
+    };
+    let mut allowed = Vec::new();
+    // whether the previous class was kept, such that a sub-scope may follow
+    let mut kept = false;
+    for class in classes.split_ascii_whitespace() {
+        kept = scope(class) || (kept && sub_scope(class));
+        if kept {
+            allowed.push(class);
+        }
+    }
+    allowed
+}
+
+enum Tag<'a> {
+    Open {
+        name: &'a str,
+        class: Option<String>,
+    },
+    Close {
+        name: &'a str,
+    },
+    // comments, doctypes and processing instructions
+    Other,
+}
+
+// Parse the tag at the start of `s` (which starts with `<`), returning it
+// along with its length, or `None` if it isn't a tag (e.g. `a < b`) or is
+// never terminated.
+fn parse_tag(s: &str) -> Option<(Tag<'_>, usize)> {
+    let rest = &s[1..];
+    if let Some(comment) = s.strip_prefix("<!--") {
+        return Some((Tag::Other, "<!--".len() + comment.find("-->")? + 3));
+    }
+    if rest.starts_with(['!', '?']) {
+        return Some((Tag::Other, rest.find('>')? + 2));
+    }
+    let (closing, rest) = match rest.strip_prefix('/') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let name_len = rest
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(rest.len());
+    if name_len == 0 || !rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let name = &rest[..name_len];
+    let mut attrs = &rest[name_len..];
+    let mut class = None;
+    loop {
+        attrs = attrs
+            .trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '/');
+        if attrs.starts_with('>') {
+            let len = s.len() - attrs.len() + 1;
+            let tag = if closing {
+                Tag::Close { name }
+            } else {
+                Tag::Open { name, class }
+            };
+            return Some((tag, len));
+        }
+        if attrs.is_empty() {
+            return None;
+        }
+        let attr_len = attrs
+            .find(|c: char| {
+                c.is_ascii_whitespace() || matches!(c, '=' | '>' | '/')
+            })
+            .unwrap_or(attrs.len())
+            .max(1);
+        let attr = &attrs[..attr_len];
+        attrs = &attrs[attr_len..];
+        let value = match attrs.trim_start().strip_prefix('=') {
+            Some(after) => {
+                let after = after.trim_start();
+                let (value, len) = match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let end = after[1..].find(quote)? + 1;
+                        (&after[1..end], end + 1)
+                    }
+                    _ => {
+                        let end = after
+                            .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                            .unwrap_or(after.len());
+                        (&after[..end], end)
+                    }
+                };
+                attrs = &after[len..];
+                Some(value)
+            }
+            None => None,
+        };
+        if attr.eq_ignore_ascii_case("class") {
+            class = value.map(|v| html_escape::decode_html_entities(v).into());
+        }
+    }
+}
+
+/// Sanitize the highlighted `html`, such that the result only consists of
+/// escaped text and `<span>` elements with the `hljs-*` classes.
+pub fn sanitize(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    // whether each of the currently open spans were kept
+    let mut spans = Vec::<bool>::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let text_len = rest.find('<').unwrap_or(rest.len());
+        let (text, tag) = rest.split_at(text_len);
+        html_escape::encode_text_to_string(
+            html_escape::decode_html_entities(text),
+            &mut out,
+        );
+        rest = tag;
+        if rest.is_empty() {
+            break;
+        }
+        let Some((tag, len)) = parse_tag(rest) else {
+            out.push_str("&lt;");
+            rest = &rest[1..];
+            continue;
+        };
+        rest = &rest[len..];
+        match tag {
+            Tag::Open { name, class } if name.eq_ignore_ascii_case("span") => {
+                let classes =
+                    allowed_classes(class.as_deref().unwrap_or_default());
+                let keep = !classes.is_empty();
+                if keep {
+                    out.push_str("<span class=\"");
+                    out.push_str(&classes.join(" "));
+                    out.push_str("\">");
+                }
+                spans.push(keep);
+            }
+            // unbalanced closing tags are dropped
+            Tag::Close { name } if name.eq_ignore_ascii_case("span") => {
+                let kept = spans.pop() == Some(true);
+                if kept {
+                    out.push_str("</span>");
+                }
+            }
+            _ => {}
+        }
+    }
+    for kept in spans.into_iter().rev() {
+        if kept {
+            out.push_str("</span>");
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn keeps_highlight_markup() {
+        let html = "<span class=\"hljs-keyword\">fn</span> \
+                    <span class=\"hljs-title function_\">main</span>() \
+                    <span class=\"hljs-title class_ inherited__\">A</span> \
+                    {&lt;&amp;&gt;}";
+        assert_eq!(sanitize(html), html);
+    }
+
+    #[test]
+    fn strips_hostile_markup() {
+        for (html, expected) in [
+            ("<script>alert(1)</script>", "alert(1)"),
+            ("<img src=x onerror=alert(1)>", ""),
+            (
+                "<span class=\"hljs-string\" onclick=\"alert(1)\">x</span>",
+                "<span class=\"hljs-string\">x</span>",
+            ),
+            (
+                "<span class=\"evil hljs-number\">1</span>",
+                "<span class=\"hljs-number\">1</span>",
+            ),
+            ("<span class=\"evil\">x</span></span>", "x"),
+            ("<span class=\"function_\">x</span>", "x"),
+            (
+                "<span class=\"evil function_ hljs-x\">1</span>",
+                "<span class=\"hljs-x\">1</span>",
+            ),
+            (
+                "<span class=\"hljs-title evil function_\">x</span>",
+                "<span class=\"hljs-title\">x</span>",
+            ),
+            (
+                "<span class='hljs-x'>open",
+                "<span class=\"hljs-x\">open</span>",
+            ),
+            ("<span class=\"hljs-x&quot; onclick=&quot;y\">z</span>", "z"),
+            ("a < b && c > d", "a &lt; b &amp;&amp; c &gt; d"),
+            ("<!-- <span class=\"hljs-x\"> -->x", "x"),
+            ("<span class=\"hljs-x\"", "&lt;span class=\"hljs-x\""),
+            ("&lt;script&gt;", "&lt;script&gt;"),
+            ("<a href=\"javascript:alert(1)\">x</a>", "x"),
+        ] {
+            assert_eq!(sanitize(html), expected, "{html}");
+        }
+    }
+
+    // Check that the output consists of nothing but text without any `<`
+    // and balanced spans with allowed classes.
+    fn well_formed(html: &str) -> bool {
+        let mut depth = 0usize;
+        let mut rest = html;
+        while let Some(start) = rest.find('<') {
+            rest = &rest[start..];
+            if let Some(after) = rest.strip_prefix("</span>") {
+                let Some(d) = depth.checked_sub(1) else {
+                    return false;
+                };
+                depth = d;
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("<span class=\"") {
+                let Some(end) = after.find("\">") else {
+                    return false;
+                };
+                let classes = &after[..end];
+                if allowed_classes(classes)
+                    != classes.split(' ').collect::<Vec<_>>()
+                {
+                    return false;
+                }
+                depth += 1;
+                rest = &after[end + 2..];
+            } else {
+                return false;
+            }
+        }
+        depth == 0
+    }
+
+    // Fragments of markup for the generated input to be made of, so that
+    // it gets to exercise the parsing of tags rather than only text.
+    fn fragment() -> impl Strategy<Value = String> {
+        prop_oneof![
+            Just("<span class=\"hljs-keyword\">".to_string()),
+            Just("<span class=\"evil\">".to_string()),
+            Just("<span class=\"function_ hljs-a class_\">".to_string()),
+            Just("<span class='hljs-a hljs-b'>".to_string()),
+            Just("</span>".to_string()),
+            Just("<script>".to_string()),
+            Just("<img src=x onerror=alert(1)>".to_string()),
+            Just("<!--".to_string()),
+            Just("-->".to_string()),
+            Just("\"".to_string()),
+            Just("&lt;".to_string()),
+            Just("&#60;".to_string()),
+            Just("<".to_string()),
+            Just(">".to_string()),
+            Just("=".to_string()),
+            "[ -~]{0,8}",
+            any::<String>(),
+        ]
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2048))]
+
+        #[test]
+        fn sanitized_is_well_formed(
+            fragments in prop::collection::vec(fragment(), 0..24)
+        ) {
+            let html = fragments.concat();
+            let sanitized = sanitize(&html);
+            prop_assert!(well_formed(&sanitized), "{html:?} => {sanitized:?}");
+            prop_assert_eq!(sanitize(&sanitized), sanitized);
+        }
+
+        #[test]
+        fn sub_scopes_only_follow_scopes(
+            classes in prop::collection::vec(
+                prop_oneof![
+                    Just("hljs-title"),
+                    Just("function_"),
+                    Just("class_"),
+                    Just("inherited__"),
+                    Just("evil"),
+                ],
+                0..6,
+            )
+        ) {
+            let html = format!("<span class=\"{}\">x</span>", classes.join(" "));
+            let sanitized = sanitize(&html);
+            let kept = sanitized
+                .strip_prefix("<span class=\"")
+                .and_then(|rest| rest.split_once('"'))
+                .map_or(vec![], |(kept, _)| kept.split(' ').collect());
+            // the kept classes start with a scope, and every sub-scope among
+            // them directly followed the class kept before it in the input
+            prop_assert!(
+                kept.first().is_none_or(|class| class.starts_with("hljs-")),
+                "{html:?} => {sanitized:?}"
+            );
+            for pair in kept.windows(2) {
+                prop_assert!(
+                    pair[1].starts_with("hljs-") || classes.windows(2).any(|w| w == pair),
+                    "{html:?} => {sanitized:?}"
+                );
+            }
+        }
+
+        #[test]
+        fn escaped_text_is_preserved(text in any::<String>()) {
+            let escaped = html_escape::encode_text(&text).into_owned();
+            prop_assert_eq!(sanitize(&escaped), escaped);
+        }
+    }
+}
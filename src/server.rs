@@ -0,0 +1,163 @@
+// The construction of the axum `Router` serving the application, kept in the
+// library (rather than in `main`) so that it may also be driven in-process
+// by the tests.
+use crate::{
+    api::{self, CodeError, RAW_CODE_PATH},
+    app::{shell, App},
+    assets::{self, CacheMode, Encoding},
+    latency, race, server_timing,
+    source::SharedCodeSource,
+    ssr_mode, telemetry, trace,
+};
+use axum::{
+    extract::{DefaultBodyLimit, Path},
+    http::{
+        header::{self, HeaderMap, HeaderValue},
+        StatusCode,
+    },
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use leptos::prelude::*;
+use leptos_axum::{generate_route_list, LeptosRoutes};
+use tower_http::services::ServeDir;
+
+/// The router for the application, with the code examples provided by the
+/// `code_source`.  The latency cycles in `latency` must be set up before any
+/// request for `highlight.js` is served.
+pub fn router(
+    leptos_options: LeptosOptions,
+    code_source: SharedCodeSource,
+) -> Router {
+    // Generate the list of routes in your Leptos App
+    let routes = generate_route_list(App);
+    let pkg_dir = std::path::Path::new(leptos_options.site_root.as_ref())
+        .join(leptos_options.site_pkg_dir.as_ref());
+
+    let hljs_module = assets::hljs_module_url(
+        &pkg_dir,
+        &format!("/{}", leptos_options.site_pkg_dir),
+    );
+
+    let raw_source = code_source.clone();
+    // the pages are rendered within their own span, and timed
+    let pages = Router::new()
+        .leptos_routes_with_context(
+            &leptos_options,
+            routes,
+            move || {
+                provide_context(code_source.clone());
+                if let Some(url) = hljs_module.clone() {
+                    provide_context(url);
+                }
+            },
+            {
+                let leptos_options = leptos_options.clone();
+                move || shell(leptos_options.clone())
+            },
+        )
+        .route_layer(middleware::from_fn(server_timing::ssr_render));
+
+    let app = Router::new()
+        .route("/highlight.min.js", get(highlight_js))
+        .route(
+            &format!("{RAW_CODE_PATH}/*id"),
+            get(move |Path(id): Path<String>| raw_code(raw_source, id)),
+        )
+        .route(
+            telemetry::TELEMETRY_PATH,
+            post(telemetry::receive)
+                .layer(DefaultBodyLimit::max(telemetry::MAX_REPORT_BYTES)),
+        );
+    // the driver's control over the gates is only exposed when enabled
+    let app = if race::enabled() {
+        app.merge(race::router())
+    } else {
+        app
+    };
+    let app = app
+        // The complete vendored highlight.js tree, which may be switched to
+        // `CacheMode::Bust` to have it behave like the above route.
+        .nest(
+            "/hljs",
+            assets::router(
+                assets::HLJS_ROOT,
+                assets::HLJS_PRECOMPRESSED,
+                CacheMode::Immutable,
+            ),
+        )
+        .nest_service(
+            &format!("/{}", leptos_options.site_pkg_dir),
+            ServeDir::new(&pkg_dir)
+                .precompressed_br()
+                .precompressed_gzip(),
+        )
+        .merge(pages)
+        .fallback(leptos_axum::file_and_error_handler(shell))
+        .layer(middleware::from_fn(latency::latency_for_highlight_js))
+        .layer(middleware::from_fn(race::gate_wasm))
+        .layer(middleware::from_fn(ssr_mode::select_ssr_mode))
+        .layer(middleware::from_fn(server_timing::server_timing))
+        .with_state(leptos_options);
+    trace::traced(app)
+}
+
+async fn highlight_js(headers: HeaderMap) -> impl IntoResponse {
+    let (encoding, body): (_, &'static [u8]) =
+        match Encoding::negotiate(&headers) {
+            Some(Encoding::Brotli) => (
+                Some(Encoding::Brotli),
+                include_bytes!(concat!(
+                    env!("OUT_DIR"),
+                    "/precompressed/highlight.min.js.br"
+                )),
+            ),
+            Some(Encoding::Gzip) => (
+                Some(Encoding::Gzip),
+                include_bytes!(concat!(
+                    env!("OUT_DIR"),
+                    "/precompressed/highlight.min.js.gz"
+                )),
+            ),
+            None => (
+                None,
+                include_bytes!(
+                    "../node_modules/@highlightjs/cdn-assets/highlight.min.js"
+                ),
+            ),
+        };
+    let mut res =
+        ([(header::CONTENT_TYPE, "text/javascript")], body).into_response();
+    let headers = res.headers_mut();
+    headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    if let Some(encoding) = encoding {
+        headers.insert(header::CONTENT_ENCODING, encoding.header_value());
+    }
+    res
+}
+
+// The shown lines of the code as plain text, which the copy buttons link to
+// such that they still do something useful without a (working) client.
+async fn raw_code(source: SharedCodeSource, id: String) -> Response {
+    match api::load_code(source, id, None).await {
+        Ok(code) => (
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            code.source,
+        )
+            .into_response(),
+        Err(err) => {
+            let status = match err {
+                ServerFnError::WrappedServerError(CodeError::NotFound(_)) => {
+                    StatusCode::NOT_FOUND
+                }
+                ServerFnError::WrappedServerError(CodeError::Forbidden(_)) => {
+                    StatusCode::FORBIDDEN
+                }
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, err.to_string()).into_response()
+        }
+    }
+}
@@ -0,0 +1,152 @@
+// The `Server-Timing` header, for the browser dev tools to show where the
+// time went in serving a request: the rendering of the page (`ssr-render`),
+// the loading of the code by `fetch_code` (`fetch_code`) and the latency
+// injected into `highlight.js` (`injected-latency`).
+//
+// Each request is given a `ServerTiming` through its extensions by the
+// `server_timing` middleware, into which the durations are recorded as they
+// complete.  As the header goes out with the head of the response, only what
+// completed before then is included, so the streaming SSR modes omit the
+// `fetch_code` calls that resolve while the body is streamed, and their
+// `ssr-render` only measures the time to the headers (as its `desc` says).
+use axum::{
+    extract::Request,
+    http::{request::Parts, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    collections::HashSet,
+    future::Future,
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::Instrument;
+
+pub const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+
+/// The description of `ssr-render`, which in the streaming SSR modes ends as
+/// the head of the response is sent rather than once the page is complete.
+pub const SSR_RENDER_DESC: &str =
+    "page render (time to headers only when streamed)";
+
+type Metric = (&'static str, Option<&'static str>, Duration);
+
+/// The durations recorded for a request, in the order they completed.
+#[derive(Clone, Debug, Default)]
+pub struct ServerTiming(Arc<Mutex<Vec<Metric>>>);
+
+impl ServerTiming {
+    pub fn record(&self, name: &'static str, duration: Duration) {
+        self.push((name, None, duration));
+    }
+
+    /// As `record`, with the `desc` shown alongside the metric.
+    pub fn record_with_desc(
+        &self,
+        name: &'static str,
+        desc: &'static str,
+        duration: Duration,
+    ) {
+        self.push((name, Some(desc), duration));
+    }
+
+    fn push(&self, metric: Metric) {
+        self.0.lock().expect("server timing poisoned").push(metric);
+    }
+
+    /// The value of the `Server-Timing` header, if anything was recorded.
+    pub fn header_value(&self) -> Option<HeaderValue> {
+        let metrics = self.0.lock().expect("server timing poisoned");
+        if metrics.is_empty() {
+            return None;
+        }
+        let value = metrics
+            .iter()
+            .map(|(name, desc, duration)| {
+                let dur = duration.as_secs_f64() * 1000.0;
+                match desc {
+                    Some(desc) => {
+                        format!("{name};desc=\"{desc}\";dur={dur:.1}")
+                    }
+                    None => format!("{name};dur={dur:.1}"),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        HeaderValue::from_str(&value).ok()
+    }
+}
+
+pub async fn server_timing(mut req: Request, next: Next) -> Response {
+    let timing = ServerTiming::default();
+    req.extensions_mut().insert(timing.clone());
+    let mut res = next.run(req).await;
+    if let Some(value) = timing.header_value() {
+        res.headers_mut().append(SERVER_TIMING, value);
+    }
+    res
+}
+
+/// Time the rendering of the pages, as a layer of their routes.  The server
+/// fns are routed alongside the pages by `leptos_axum`, so are passed through
+/// untimed.
+pub async fn ssr_render(req: Request, next: Next) -> Response {
+    static SERVER_FNS: LazyLock<HashSet<&'static str>> = LazyLock::new(|| {
+        leptos::server_fn::axum::server_fn_paths()
+            .map(|(path, _)| path)
+            .collect()
+    });
+    if SERVER_FNS.contains(req.uri().path()) {
+        return next.run(req).await;
+    }
+    let timing = req.extensions().get::<ServerTiming>().cloned();
+    let span = tracing::info_span!("ssr_render", path = req.uri().path());
+    let start = Instant::now();
+    let res = next.run(req).instrument(span).await;
+    if let Some(timing) = timing {
+        timing.record_with_desc("ssr-render", SSR_RENDER_DESC, start.elapsed());
+    }
+    res
+}
+
+/// Run the `fut` in the `span`, recording its duration as `name` for the
+/// request being served, as found in the Leptos context (i.e. from within a
+/// server function or the rendering).
+pub async fn timed<T>(
+    name: &'static str,
+    span: tracing::Span,
+    fut: impl Future<Output = T>,
+) -> T {
+    let timing = leptos::prelude::use_context::<Parts>()
+        .and_then(|parts| parts.extensions.get::<ServerTiming>().cloned());
+    let start = Instant::now();
+    let output = fut.instrument(span).await;
+    if let Some(timing) = timing {
+        timing.record(name, start.elapsed());
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_lists_every_metric() {
+        let timing = ServerTiming::default();
+        assert_eq!(timing.header_value(), None);
+        timing.record("fetch_code", Duration::from_micros(50_240));
+        timing.record("fetch_code", Duration::from_millis(51));
+        timing.record_with_desc(
+            "ssr-render",
+            "time to headers",
+            Duration::from_millis(120),
+        );
+        assert_eq!(
+            timing.header_value().unwrap(),
+            "fetch_code;dur=50.2, fetch_code;dur=51.0, \
+             ssr-render;desc=\"time to headers\";dur=120.0"
+        );
+    }
+}
@@ -0,0 +1,663 @@
+// The sources of code that `fetch_code` may load from on the server, which
+// is provided to the server functions and the SSR renderer as context in
+// the form of `SharedCodeSource`.
+use crate::{
+    api::{Code, CodeError},
+    consts,
+};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Cursor, Read, Write},
+    path::{Component, Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Arc,
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SourceError {
+    #[error("code `{0}` not found")]
+    NotFound(String),
+    #[error("invalid code id `{0}`")]
+    InvalidId(String),
+    #[error("access to code `{0}` is forbidden")]
+    Forbidden(String),
+    #[error("code `{id}` is {size} bytes, exceeding the limit of {max}")]
+    TooLarge { id: String, size: u64, max: u64 },
+    #[error("code `{id}` is `{actual}` rather than `{requested}`")]
+    LangMismatch {
+        id: String,
+        requested: String,
+        actual: String,
+    },
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("git error: {0}")]
+    Git(String),
+}
+
+impl From<SourceError> for CodeError {
+    fn from(err: SourceError) -> Self {
+        match err {
+            SourceError::NotFound(_) => CodeError::NotFound(err.to_string()),
+            SourceError::Forbidden(_) => CodeError::Forbidden(err.to_string()),
+            // Don't leak the details of the server's environment.
+            SourceError::Io(_) | SourceError::Git(_) => {
+                CodeError::Other("failed to load code".to_string())
+            }
+            _ => CodeError::Other(err.to_string()),
+        }
+    }
+}
+
+/// A place where code may be loaded from by its `id`.
+///
+/// The `lang` is the language the caller expects the code to be in, which
+/// sources may use to reject code that is in some other language.
+pub trait CodeSource: Send + Sync {
+    fn fetch(&self, id: &str, lang: Option<&str>) -> Result<Code, SourceError>;
+
+    /// Open the code for reading it incrementally (e.g. for streaming it),
+    /// rather than loading it in its entirety.  This falls back to reading
+    /// what `fetch` provides for the sources that have no better way.
+    fn read(
+        &self,
+        id: &str,
+        lang: Option<&str>,
+    ) -> Result<CodeReader, SourceError> {
+        let code = self.fetch(id, lang)?;
+        Ok(CodeReader {
+            lang: code.lang,
+            reader: Box::new(Cursor::new(code.source.into_bytes())),
+        })
+    }
+}
+
+/// Code being read, as provided by `CodeSource::read`.
+pub struct CodeReader {
+    pub lang: String,
+    pub reader: Box<dyn BufRead + Send>,
+}
+
+pub type SharedCodeSource = Arc<dyn CodeSource>;
+
+fn check_lang(
+    code: Code,
+    id: &str,
+    lang: Option<&str>,
+) -> Result<Code, SourceError> {
+    expect_lang(&code.lang, id, lang)?;
+    Ok(code)
+}
+
+fn expect_lang(
+    actual: &str,
+    id: &str,
+    lang: Option<&str>,
+) -> Result<(), SourceError> {
+    match lang {
+        Some(lang) if lang != actual => Err(SourceError::LangMismatch {
+            id: id.to_string(),
+            requested: lang.to_string(),
+            actual: actual.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// The language name as understood by highlight.js for the extension of
+/// the file at `path`, falling back to `plaintext`.
+pub fn lang_from_path(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("rs") => "rust",
+        Some("js" | "mjs" | "cjs") => "javascript",
+        Some("ts") => "typescript",
+        Some("html" | "xml" | "svg") => "xml",
+        Some("css") => "css",
+        Some("scss") => "scss",
+        Some("json") => "json",
+        Some("toml") => "ini",
+        Some("yaml" | "yml") => "yaml",
+        Some("md") => "markdown",
+        Some("py") => "python",
+        Some("sh") => "bash",
+        Some("c" | "h") => "c",
+        Some("cpp" | "hpp" | "cc") => "cpp",
+        Some("go") => "go",
+        _ => "plaintext",
+    }
+}
+
+// Only accept ids that are plain relative paths, such that they can't be
+// used to reach outside of the root of a source.
+fn relative_path(id: &str) -> Result<&Path, SourceError> {
+    let path = Path::new(id);
+    if id.is_empty() {
+        return Err(SourceError::InvalidId(id.to_string()));
+    }
+    if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(SourceError::Forbidden(id.to_string()));
+    }
+    Ok(path)
+}
+
+/// Code held in memory, keyed by id.
+#[derive(Clone, Debug, Default)]
+pub struct MemorySource(pub HashMap<String, Code>);
+
+impl MemorySource {
+    /// The example programs provided by `consts`.
+    pub fn consts() -> Self {
+        Self(
+            [
+                ("ch03_05a", consts::CH03_05A, "rust"),
+                ("ch05_02a", consts::CH05_02A, "rust"),
+                ("markdown_demo.md", consts::MARKDOWN_DEMO, "markdown"),
+            ]
+            .into_iter()
+            .map(|(id, source, lang)| (id.to_string(), Code::new(source, lang)))
+            .collect(),
+        )
+    }
+}
+
+impl CodeSource for MemorySource {
+    fn fetch(&self, id: &str, lang: Option<&str>) -> Result<Code, SourceError> {
+        let code = self
+            .0
+            .get(id)
+            .cloned()
+            .ok_or_else(|| SourceError::NotFound(id.to_string()))?;
+        check_lang(code, id, lang)
+    }
+}
+
+/// Code from files under a directory, where the id is the path of the file
+/// relative to the root.
+///
+/// Only regular files that resolve to somewhere under the root (after
+/// following any symlinks) and are no larger than the maximum size may be
+/// read.
+#[derive(Clone, Debug)]
+pub struct DirSource {
+    root: PathBuf,
+    max_size: u64,
+}
+
+impl DirSource {
+    pub const DEFAULT_MAX_SIZE: u64 = 1024 * 1024;
+
+    pub fn new(root: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self {
+            root: root.as_ref().canonicalize()?,
+            max_size: Self::DEFAULT_MAX_SIZE,
+        })
+    }
+
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+
+impl DirSource {
+    // Open the file for the `id`, which must be a regular file under the
+    // root that isn't too large, returning it along with its resolved path.
+    fn open(&self, id: &str) -> Result<(File, PathBuf), SourceError> {
+        let not_found = |err: std::io::Error| match err.kind() {
+            std::io::ErrorKind::NotFound => {
+                SourceError::NotFound(id.to_string())
+            }
+            _ => err.into(),
+        };
+        let path = self
+            .root
+            .join(relative_path(id)?)
+            .canonicalize()
+            .map_err(not_found)?;
+        if !path.starts_with(&self.root) {
+            return Err(SourceError::Forbidden(id.to_string()));
+        }
+        // Everything from here on is checked against the file that was
+        // opened, rather than whatever the path may point to by then.
+        let file = open(&path).map_err(not_found)?;
+        if !opened_path(&file, &path)?.starts_with(&self.root) {
+            return Err(SourceError::Forbidden(id.to_string()));
+        }
+        let metadata = file.metadata()?;
+        if !metadata.is_file() {
+            return Err(SourceError::NotFound(id.to_string()));
+        }
+        if metadata.len() > self.max_size {
+            return Err(self.too_large(id, metadata.len()));
+        }
+        Ok((file, path))
+    }
+
+    fn too_large(&self, id: &str, size: u64) -> SourceError {
+        SourceError::TooLarge {
+            id: id.to_string(),
+            size,
+            max: self.max_size,
+        }
+    }
+}
+
+impl CodeSource for DirSource {
+    fn fetch(&self, id: &str, lang: Option<&str>) -> Result<Code, SourceError> {
+        let (file, path) = self.open(id)?;
+        // the file may have grown since
+        let mut source = String::new();
+        file.take(self.max_size + 1).read_to_string(&mut source)?;
+        if source.len() as u64 > self.max_size {
+            return Err(self.too_large(id, source.len() as u64));
+        }
+        check_lang(Code::new(source, lang_from_path(&path)), id, lang)
+    }
+
+    fn read(
+        &self,
+        id: &str,
+        lang: Option<&str>,
+    ) -> Result<CodeReader, SourceError> {
+        let (file, path) = self.open(id)?;
+        let actual = lang_from_path(&path);
+        expect_lang(actual, id, lang)?;
+        Ok(CodeReader {
+            lang: actual.to_string(),
+            // whatever the file may have grown by since is left out
+            reader: Box::new(BufReader::new(file.take(self.max_size))),
+        })
+    }
+}
+
+// Open the file without following a symlink in its place, and without
+// blocking on a FIFO (which is then rejected as not being a regular file).
+fn open(path: &Path) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.read(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK);
+    }
+    options.open(path)
+}
+
+// The path of the file that was actually opened, such that the check against
+// the root can't be raced by swapping one of the directories of the resolved
+// `path` for a symlink before it was opened.  Where that isn't known, the
+// resolved path is all there is to go by.
+#[cfg(target_os = "linux")]
+fn opened_path(file: &File, _path: &Path) -> std::io::Result<PathBuf> {
+    use std::os::fd::AsRawFd;
+    std::fs::read_link(format!("/proc/self/fd/{}", file.as_raw_fd()))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn opened_path(_file: &File, path: &Path) -> std::io::Result<PathBuf> {
+    Ok(path.to_path_buf())
+}
+
+/// Code from files in a local git repository at some revision, where the id
+/// is the path of the file relative to the root of the repository.
+///
+/// As with `DirSource`, only files no larger than the maximum size may be
+/// read.
+#[derive(Clone, Debug)]
+pub struct GitSource {
+    repo: PathBuf,
+    rev: String,
+    max_size: u64,
+}
+
+impl GitSource {
+    pub const DEFAULT_MAX_SIZE: u64 = DirSource::DEFAULT_MAX_SIZE;
+
+    pub fn new(repo: impl Into<PathBuf>, rev: impl Into<String>) -> Self {
+        Self {
+            repo: repo.into(),
+            rev: rev.into(),
+            max_size: Self::DEFAULT_MAX_SIZE,
+        }
+    }
+
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+
+impl GitSource {
+    // git in the repository, with its messages kept out of the host's locale
+    fn git(&self) -> Command {
+        let mut git = Command::new("git");
+        git.env("LC_ALL", "C").arg("-C").arg(&self.repo);
+        git
+    }
+
+    // Look up the blob for the `id` at the revision, which must not be too
+    // large, returning its object name.  The lookup is reported on stdout in
+    // a fixed format, so a missing path (or revision) is known without going
+    // by the messages of git.
+    fn blob(&self, id: &str) -> Result<String, SourceError> {
+        if id.contains('\n') {
+            return Err(SourceError::InvalidId(id.to_string()));
+        }
+        let mut child = self
+            .git()
+            .arg("cat-file")
+            .arg("--batch-check=%(objectname) %(objecttype) %(objectsize)")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(format!("{}:{id}\n", self.rev).as_bytes())?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(git_error(&output.stderr));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        match stdout.trim_end().split(' ').collect::<Vec<_>>()[..] {
+            [name, "blob", size] => {
+                let size = size
+                    .parse()
+                    .map_err(|_| SourceError::Git(stdout.to_string()))?;
+                if size > self.max_size {
+                    return Err(SourceError::TooLarge {
+                        id: id.to_string(),
+                        size,
+                        max: self.max_size,
+                    });
+                }
+                Ok(name.to_string())
+            }
+            // a directory, or nothing at all
+            _ => Err(SourceError::NotFound(id.to_string())),
+        }
+    }
+}
+
+impl CodeSource for GitSource {
+    fn fetch(&self, id: &str, lang: Option<&str>) -> Result<Code, SourceError> {
+        let path = relative_path(id)?;
+        let blob = self.blob(id)?;
+        // the blob is read by its object name, so is the one that was sized
+        let output = self.git().args(["cat-file", "blob", &blob]).output()?;
+        if !output.status.success() {
+            return Err(git_error(&output.stderr));
+        }
+        let code = Code::new(
+            String::from_utf8(output.stdout)
+                .map_err(|err| SourceError::Git(err.to_string()))?,
+            lang_from_path(path),
+        );
+        check_lang(code, id, lang)
+    }
+}
+
+fn git_error(stderr: &[u8]) -> SourceError {
+    SourceError::Git(String::from_utf8_lossy(stderr).trim().to_string())
+}
+
+/// The ways in which the configuration of the source may be invalid.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error(
+        "unsupported CODE_SOURCE `{0}`, expected `dir:<path>` or \
+         `git:<repo>@<rev>`"
+    )]
+    Unsupported(String),
+    #[error("CODE_SOURCE directory `{path}` is inaccessible: {err}")]
+    Dir {
+        path: String,
+        #[source]
+        err: std::io::Error,
+    },
+    #[error("CODE_SOURCE_MAX_SIZE `{0}` isn't a size in bytes")]
+    MaxSize(String),
+}
+
+/// Configure the source from the `CODE_SOURCE` environment variable, which
+/// may be `dir:<path>` or `git:<repo>@<rev>`, with the example programs in
+/// memory being the default.  The maximum size of files read from a `dir:`
+/// or `git:` source may be set through `CODE_SOURCE_MAX_SIZE`.
+pub fn from_env() -> Result<SharedCodeSource, ConfigError> {
+    from_config(
+        std::env::var("CODE_SOURCE").ok().as_deref(),
+        std::env::var("CODE_SOURCE_MAX_SIZE").ok().as_deref(),
+    )
+}
+
+/// Configure the source as `from_env` does, from the values of its
+/// variables.
+pub fn from_config(
+    code_source: Option<&str>,
+    max_size: Option<&str>,
+) -> Result<SharedCodeSource, ConfigError> {
+    let Some(value) = code_source else {
+        return Ok(Arc::new(MemorySource::consts()));
+    };
+    let max_size = max_size
+        .map(|max| {
+            max.parse()
+                .map_err(|_| ConfigError::MaxSize(max.to_string()))
+        })
+        .transpose()?;
+    if let Some(path) = value.strip_prefix("dir:") {
+        let source = DirSource::new(path).map_err(|err| ConfigError::Dir {
+            path: path.to_string(),
+            err,
+        })?;
+        Ok(Arc::new(match max_size {
+            Some(max) => source.max_size(max),
+            None => source,
+        }))
+    } else if let Some(spec) = value.strip_prefix("git:") {
+        let (repo, rev) = spec.rsplit_once('@').unwrap_or((spec, "HEAD"));
+        let source = GitSource::new(repo, rev);
+        Ok(Arc::new(match max_size {
+            Some(max) => source.max_size(max),
+            None => source,
+        }))
+    } else {
+        Err(ConfigError::Unsupported(value.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A scratch directory containing a `root` to be served, with a secret
+    // file that sits outside of it.
+    fn scratch() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("root/src")).unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        std::fs::write(dir.path().join("root/src/main.rs"), "fn main() {}\n")
+            .unwrap();
+        dir
+    }
+
+    #[test]
+    fn dir_source_reads_files_under_root() {
+        let scratch = scratch();
+        let dir = scratch.path();
+        let source = DirSource::new(dir.join("root")).unwrap();
+        assert_eq!(
+            source.fetch("src/main.rs", None).unwrap(),
+            Code::new("fn main() {}\n", "rust")
+        );
+        assert!(matches!(
+            source.fetch("src/main.rs", Some("javascript")),
+            Err(SourceError::LangMismatch { .. })
+        ));
+        assert!(matches!(
+            source.fetch("src/lib.rs", None),
+            Err(SourceError::NotFound(_))
+        ));
+        assert!(matches!(
+            source.fetch("src", None),
+            Err(SourceError::NotFound(_))
+        ));
+
+        let mut code = source.read("src/main.rs", None).unwrap();
+        let mut read = String::new();
+        code.reader.read_to_string(&mut read).unwrap();
+        assert_eq!(
+            (code.lang.as_str(), read.as_str()),
+            ("rust", "fn main() {}\n")
+        );
+        assert!(matches!(
+            source.read("src/main.rs", Some("javascript")),
+            Err(SourceError::LangMismatch { .. })
+        ));
+        assert!(matches!(
+            source.read("../secret.txt", None),
+            Err(SourceError::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn dir_source_rejects_escapes() {
+        let scratch = scratch();
+        let dir = scratch.path();
+        let source = DirSource::new(dir.join("root")).unwrap();
+        for id in ["../secret.txt", "src/../../secret.txt", "/etc/passwd"] {
+            assert!(
+                matches!(
+                    source.fetch(id, None),
+                    Err(SourceError::Forbidden(_))
+                ),
+                "{id} should be forbidden"
+            );
+        }
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(
+                dir.join("secret.txt"),
+                dir.join("root/link.txt"),
+            )
+            .unwrap();
+            assert!(matches!(
+                source.fetch("link.txt", None),
+                Err(SourceError::Forbidden(_))
+            ));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dir_source_rejects_special_files() {
+        let scratch = scratch();
+        let dir = scratch.path();
+        let fifo = dir.join("root/fifo");
+        let path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o600) }, 0);
+        let source = DirSource::new(dir.join("root")).unwrap();
+        // without blocking on the lack of a writer
+        assert!(matches!(
+            source.fetch("fifo", None),
+            Err(SourceError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn configured_from_env() {
+        let scratch = scratch();
+        let dir = scratch.path();
+        let root = format!("dir:{}", dir.join("root").display());
+        let source = from_config(None, None).unwrap();
+        assert!(source.fetch("ch03_05a", None).is_ok());
+        let source = from_config(Some(&root), None).unwrap();
+        assert!(source.fetch("src/main.rs", None).is_ok());
+        let source = from_config(Some(&root), Some("4")).unwrap();
+        assert!(matches!(
+            source.fetch("src/main.rs", None),
+            Err(SourceError::TooLarge { max: 4, .. })
+        ));
+        assert!(from_config(Some("git:."), None).is_ok());
+
+        assert!(matches!(
+            from_config(Some("dir:/nonexistent/axum_js_ssr"), None),
+            Err(ConfigError::Dir { .. })
+        ));
+        assert!(matches!(
+            from_config(Some(&root), Some("1MiB")),
+            Err(ConfigError::MaxSize(_))
+        ));
+        assert!(matches!(
+            from_config(Some("s3://bucket"), None),
+            Err(ConfigError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn dir_source_enforces_max_size() {
+        let scratch = scratch();
+        let dir = scratch.path();
+        let source = DirSource::new(dir.join("root")).unwrap().max_size(4);
+        assert!(matches!(
+            source.fetch("src/main.rs", None),
+            Err(SourceError::TooLarge {
+                size: 13,
+                max: 4,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn git_source_reads_blobs_at_rev() {
+        let scratch = scratch();
+        let root = scratch.path().join("root");
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(&root)
+                .args(["-c", "user.name=test", "-c", "user.email=test@test"])
+                .args(["-c", "commit.gpgsign=false"])
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {args:?}");
+        };
+        git(&["init", "-q"]);
+        git(&["add", "src/main.rs"]);
+        git(&["commit", "-q", "-m", "main"]);
+        std::fs::write(root.join("src/lib.rs"), "").unwrap();
+
+        let source = GitSource::new(&root, "HEAD");
+        assert_eq!(
+            source.fetch("src/main.rs", None).unwrap(),
+            Code::new("fn main() {}\n", "rust")
+        );
+        // on disk, but not at the revision
+        assert!(matches!(
+            source.fetch("src/lib.rs", None),
+            Err(SourceError::NotFound(_))
+        ));
+        assert!(matches!(
+            source.fetch("src", None),
+            Err(SourceError::NotFound(_))
+        ));
+        assert!(matches!(
+            source.fetch("../secret.txt", None),
+            Err(SourceError::Forbidden(_))
+        ));
+        assert!(matches!(
+            source.max_size(4).fetch("src/main.rs", None),
+            Err(SourceError::TooLarge {
+                size: 13,
+                max: 4,
+                ..
+            })
+        ));
+    }
+}
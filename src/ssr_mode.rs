@@ -0,0 +1,159 @@
+// Selection of the SSR mode the examples are rendered with for the request,
+// by redirecting to the path the example is registered at under that mode
+// (see `examples`).  The mode is chosen through the `ssr` query parameter,
+// which is then remembered in the `ssr_mode` cookie for the requests that
+// follow, with `ssr=default` going back to the mode of each example.
+use crate::{
+    app::EXAMPLES,
+    compare::Frame,
+    examples::{example_at, split_ssr_mode_path, SSR_MODES, SSR_MODE_PREFIX},
+};
+use axum::{
+    extract::Request,
+    http::{
+        header::{COOKIE, LOCATION, SET_COOKIE},
+        Method, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+pub const SSR_MODE_COOKIE: &str = "ssr_mode";
+
+pub async fn select_ssr_mode(req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    let Some((example, current)) = example_at(EXAMPLES, path) else {
+        return next.run(req).await;
+    };
+    if req.method() != Method::GET {
+        return next.run(req).await;
+    }
+    let query = req.uri().query().unwrap_or_default();
+    // the frames of the comparison show the example as rendered by default,
+    // whichever mode was chosen for the page framing them
+    if Frame::from_query(query).is_some() {
+        return next.run(req).await;
+    }
+    let requested = query.split('&').find_map(|pair| pair.strip_prefix("ssr="));
+    let rest = query
+        .split('&')
+        .filter(|pair| !pair.is_empty() && !pair.starts_with("ssr="))
+        .collect::<Vec<_>>()
+        .join("&");
+    let location = |mode: Option<&str>| {
+        let path = match mode {
+            Some(mode) => {
+                format!("/{SSR_MODE_PREFIX}/{mode}/{}", example.slug)
+            }
+            None => format!("/{}", example.slug),
+        };
+        if rest.is_empty() {
+            path
+        } else {
+            format!("{path}?{rest}")
+        }
+    };
+
+    if let Some(requested) = requested {
+        let mode = known_mode(requested);
+        let cookie = match mode {
+            Some(mode) => {
+                format!("{SSR_MODE_COOKIE}={mode}; Path=/; SameSite=Lax")
+            }
+            None => format!("{SSR_MODE_COOKIE}=; Path=/; Max-Age=0"),
+        };
+        return (
+            StatusCode::SEE_OTHER,
+            [(LOCATION, location(mode)), (SET_COOKIE, cookie)],
+        )
+            .into_response();
+    }
+    // the explicitly requested mode takes precedence over the cookie, which
+    // only needs following where the example isn't rendered with its mode
+    // anyway (the navigation links staying under the mode once there)
+    if split_ssr_mode_path(path).is_none() {
+        let cookie = req
+            .headers()
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .find_map(|pair| {
+                pair.trim().strip_prefix(SSR_MODE_COOKIE)?.strip_prefix('=')
+            })
+            .and_then(known_mode)
+            .filter(|mode| *mode != current);
+        if let Some(mode) = cookie {
+            return (StatusCode::SEE_OTHER, [(LOCATION, location(Some(mode)))])
+                .into_response();
+        }
+    }
+    next.run(req).await
+}
+
+fn known_mode(name: &str) -> Option<&'static str> {
+    SSR_MODES
+        .iter()
+        .map(|(known, _)| *known)
+        .find(|known| *known == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn request(uri: &str, cookie: Option<&str>) -> Response {
+        let app = Router::new()
+            .fallback(get(|| async { "rendered" }))
+            .layer(middleware::from_fn(select_ssr_mode));
+        let mut req = Request::get(uri);
+        if let Some(cookie) = cookie {
+            req = req.header(COOKIE, cookie);
+        }
+        app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    fn header<'a>(res: &'a Response, name: &str) -> Option<&'a str> {
+        res.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    #[tokio::test]
+    async fn select_by_query_and_cookie() {
+        let res = request("/naive?ssr=in-order&x=1", None).await;
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        assert_eq!(header(&res, "location"), Some("/ssr/in-order/naive?x=1"));
+        assert_eq!(
+            header(&res, "set-cookie"),
+            Some("ssr_mode=in-order; Path=/; SameSite=Lax")
+        );
+
+        let res = request("/ssr/in-order/naive?ssr=default", None).await;
+        assert_eq!(header(&res, "location"), Some("/naive"));
+        assert_eq!(
+            header(&res, "set-cookie"),
+            Some("ssr_mode=; Path=/; Max-Age=0")
+        );
+
+        let res = request("/naive", Some("a=b; ssr_mode=in-order")).await;
+        assert_eq!(header(&res, "location"), Some("/ssr/in-order/naive"));
+
+        for (uri, cookie) in [
+            ("/ssr/async/naive", Some("ssr_mode=in-order")),
+            ("/naive", Some("ssr_mode=bogus")),
+            // the mode of the example already
+            ("/naive", Some("ssr_mode=async")),
+            ("/streaming", Some("ssr_mode=out-of-order")),
+            ("/", Some("ssr_mode=async")),
+            ("/api/fetch_code?ssr=async", None),
+            ("/naive?compare=ssr", Some("ssr_mode=in-order")),
+            ("/naive?compare=html&ssr=in-order", None),
+        ] {
+            let res = request(uri, cookie).await;
+            assert_eq!(res.status(), StatusCode::OK, "{uri}");
+        }
+    }
+}
@@ -0,0 +1,419 @@
+// Telemetry from the client: the timing of the hydration, the loading of the
+// scripts and the highlighting, along with the panics, such that the rate at
+// which the visitors run into the hydration failures the examples
+// demonstrate may be known from the field rather than only reproduced.
+//
+// The client buffers the events and beacons them as a `Report` to the
+// `/telemetry` endpoint once hydrated, upon a panic, and as the page is left.
+// The server appends each report it receives as a line of JSON to the file
+// named by `TELEMETRY_LOG` (`telemetry.jsonl` by default), which is rotated
+// once it reaches `MAX_LOG_BYTES`.  As anyone may send reports, each client
+// (by address) may only send so many of them a minute.
+use serde::{Deserialize, Serialize};
+
+/// The path of the endpoint receiving the reports.
+pub const TELEMETRY_PATH: &str = "/telemetry";
+
+/// The events beaconed in a single report, from the page at the `path`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub path: String,
+    pub events: Vec<Event>,
+}
+
+/// The times are in milliseconds since the start of the navigation to the
+/// page, as given by `performance.now()`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Event {
+    HydrationStart {
+        at: f64,
+    },
+    HydrationEnd {
+        at: f64,
+        duration: f64,
+    },
+    ScriptLoad {
+        url: String,
+        start: f64,
+        duration: f64,
+    },
+    Highlight {
+        lang: String,
+        bytes: usize,
+        duration: f64,
+        ok: bool,
+    },
+    Panic {
+        message: String,
+        location: Option<String>,
+        // whether the panic happened after the hydration completed, or
+        // rather caused it to fail
+        hydrated: bool,
+    },
+}
+
+#[cfg(not(feature = "ssr"))]
+mod csr {
+    use super::{Event, Report, TELEMETRY_PATH};
+    use std::{cell::RefCell, collections::HashSet, panic::PanicHookInfo};
+    use wasm_bindgen::{prelude::Closure, JsCast};
+
+    // Past this, the events are dropped until the next flush, such that a
+    // page left open doesn't grow the buffer without bound.
+    const MAX_PENDING: usize = 256;
+
+    thread_local! {
+        static PENDING: RefCell<Vec<Event>> = const { RefCell::new(Vec::new()) };
+        // the scripts already reported
+        static REPORTED: RefCell<HashSet<String>> =
+            RefCell::new(HashSet::new());
+    }
+
+    /// The time since the start of the navigation, where there is a window
+    /// to tell (i.e. not under the test runner).
+    pub fn now() -> Option<f64> {
+        web_sys::window()?.performance().map(|perf| perf.now())
+    }
+
+    pub fn record(event: Event) {
+        // a panic in the middle of recording may still need to be reported
+        let _ = PENDING.try_with(|pending| {
+            if let Ok(mut pending) = pending.try_borrow_mut() {
+                if pending.len() < MAX_PENDING {
+                    pending.push(event);
+                }
+            }
+        });
+    }
+
+    /// Record the highlighting of the `bytes` of code started at `start`.
+    pub fn record_highlight(
+        start: Option<f64>,
+        lang: &str,
+        bytes: usize,
+        ok: bool,
+    ) {
+        if let (Some(start), Some(end)) = (start, now()) {
+            record(Event::Highlight {
+                lang: lang.to_string(),
+                bytes,
+                duration: end - start,
+                ok,
+            });
+        }
+    }
+
+    pub fn record_panic(info: &PanicHookInfo, hydrated: bool) {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| info.to_string());
+        record(Event::Panic {
+            message,
+            location: info.location().map(|l| l.to_string()),
+            hydrated,
+        });
+    }
+
+    // The scripts (along with the WASM bundle) loaded since the last flush.
+    fn record_script_loads() {
+        let Some(perf) = web_sys::window().and_then(|w| w.performance()) else {
+            return;
+        };
+        for entry in perf.get_entries_by_type("resource") {
+            let entry = entry.unchecked_into::<web_sys::PerformanceEntry>();
+            let url = entry.name();
+            let path = url.split(['?', '#']).next().unwrap_or_default();
+            if !(path.ends_with(".js") || path.ends_with(".wasm")) {
+                continue;
+            }
+            let new = REPORTED
+                .with_borrow_mut(|reported| reported.insert(url.clone()));
+            if new {
+                record(Event::ScriptLoad {
+                    url,
+                    start: entry.start_time(),
+                    duration: entry.duration(),
+                });
+            }
+        }
+    }
+
+    /// Beacon the events recorded so far, if any.
+    pub fn flush() {
+        let Some(window) = web_sys::window() else {
+            return;
+        };
+        record_script_loads();
+        let Ok(events) = PENDING.with(|pending| {
+            pending
+                .try_borrow_mut()
+                .map(|mut pending| pending.split_off(0))
+        }) else {
+            return;
+        };
+        if events.is_empty() {
+            return;
+        }
+        let report = Report {
+            path: window.location().pathname().unwrap_or_default(),
+            events,
+        };
+        if let Ok(body) = serde_json::to_string(&report) {
+            let _ = window
+                .navigator()
+                .send_beacon_with_opt_str(TELEMETRY_PATH, Some(&body));
+        }
+    }
+
+    /// Flush whatever is left as the page is left (or hidden, which may be
+    /// the last chance on mobile).
+    pub fn flush_on_exit() {
+        let Some(window) = web_sys::window() else {
+            return;
+        };
+        let flush = Closure::<dyn Fn()>::new(flush);
+        let _ = window.add_event_listener_with_callback(
+            "pagehide",
+            flush.as_ref().unchecked_ref(),
+        );
+        let _ = window.add_event_listener_with_callback(
+            "visibilitychange",
+            flush.as_ref().unchecked_ref(),
+        );
+        flush.forget();
+    }
+}
+
+#[cfg(feature = "ssr")]
+mod ssr {
+    use super::Report;
+    use axum::{
+        extract::ConnectInfo,
+        http::{header, HeaderMap, StatusCode},
+        response::IntoResponse,
+    };
+    use serde::Serialize;
+    use std::{
+        collections::HashMap,
+        ffi::OsString,
+        io::Write,
+        net::{IpAddr, SocketAddr},
+        path::{Path, PathBuf},
+        sync::{LazyLock, Mutex},
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    };
+
+    /// The largest report accepted.
+    pub const MAX_REPORT_BYTES: usize = 64 * 1024;
+
+    /// The size the log may reach before it is rotated, with the previous
+    /// log kept alongside as `{log}.1`.
+    pub const MAX_LOG_BYTES: u64 = 16 * 1024 * 1024;
+
+    /// The number of reports a client may send within `RATE_WINDOW`.
+    pub const RATE_LIMIT: u32 = 30;
+    pub const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+    static LOG: LazyLock<PathBuf> = LazyLock::new(|| {
+        std::env::var_os("TELEMETRY_LOG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("telemetry.jsonl"))
+    });
+    // serializes the appending of the lines from the concurrent requests
+    static WRITING: Mutex<()> = Mutex::new(());
+    // the reports received from each client within its current window, where
+    // the clients without a known address (i.e. not served over TCP) are
+    // counted together
+    static CLIENTS: LazyLock<Mutex<HashMap<Option<IpAddr>, Window>>> =
+        LazyLock::new(Default::default);
+
+    struct Window {
+        start: Instant,
+        reports: u32,
+    }
+
+    // Count a report from the `client`, returning whether it is within the
+    // limit.  The windows that have ended are dropped along the way.
+    fn allow(
+        clients: &mut HashMap<Option<IpAddr>, Window>,
+        client: Option<IpAddr>,
+        now: Instant,
+    ) -> bool {
+        clients
+            .retain(|_, window| now.duration_since(window.start) < RATE_WINDOW);
+        let window = clients.entry(client).or_insert(Window {
+            start: now,
+            reports: 0,
+        });
+        window.reports += 1;
+        window.reports <= RATE_LIMIT
+    }
+
+    // The line written for each report, along with what the server knows of
+    // the client.
+    #[derive(Serialize)]
+    struct Line<'a> {
+        received_ms: u128,
+        user_agent: Option<&'a str>,
+        #[serde(flatten)]
+        report: &'a Report,
+    }
+
+    fn append(log: &Path, line: &str, max_bytes: u64) -> std::io::Result<()> {
+        let _writing = WRITING.lock().expect("telemetry log poisoned");
+        let size = std::fs::metadata(log).map_or(0, |meta| meta.len());
+        if size > 0 && size + line.len() as u64 + 1 > max_bytes {
+            let mut rotated = OsString::from(log);
+            rotated.push(".1");
+            std::fs::rename(log, rotated)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log)?;
+        writeln!(file, "{line}")
+    }
+
+    fn write_report(
+        log: &Path,
+        user_agent: Option<&str>,
+        body: &str,
+    ) -> Result<(), StatusCode> {
+        let report = serde_json::from_str::<Report>(body)
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+        let line = Line {
+            received_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis(),
+            user_agent,
+            report: &report,
+        };
+        let line = serde_json::to_string(&line)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        append(log, &line, MAX_LOG_BYTES).map_err(|err| {
+            tracing::error!(
+                "failed to write telemetry to {}: {err}",
+                log.display()
+            );
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+    }
+
+    /// Receive a report, which is sent through `navigator.sendBeacon()` and
+    /// thus as `text/plain` rather than `application/json`.
+    pub async fn receive(
+        client: Option<ConnectInfo<SocketAddr>>,
+        headers: HeaderMap,
+        body: String,
+    ) -> impl IntoResponse {
+        let client = client.map(|ConnectInfo(addr)| addr.ip());
+        let allowed = allow(
+            &mut CLIENTS.lock().expect("telemetry clients poisoned"),
+            client,
+            Instant::now(),
+        );
+        if !allowed {
+            return StatusCode::TOO_MANY_REQUESTS;
+        }
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|ua| ua.to_str().ok())
+            .map(str::to_string);
+        // the log is written through blocking calls, under a lock
+        let written = tokio::task::spawn_blocking(move || {
+            write_report(&LOG, user_agent.as_deref(), &body)
+        })
+        .await;
+        match written {
+            Ok(Ok(())) => StatusCode::NO_CONTENT,
+            Ok(Err(status)) => status,
+            Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::telemetry::Event;
+
+        #[test]
+        fn reports_written_as_json_lines() {
+            let dir = tempfile::tempdir().unwrap();
+            let log = dir.path().join("telemetry.jsonl");
+            let report = Report {
+                path: "/naive".to_string(),
+                events: vec![
+                    Event::HydrationEnd {
+                        at: 120.5,
+                        duration: 20.0,
+                    },
+                    Event::Panic {
+                        message: "unreachable".to_string(),
+                        location: None,
+                        hydrated: false,
+                    },
+                ],
+            };
+            let body = serde_json::to_string(&report).unwrap();
+            write_report(&log, Some("test-agent"), &body).unwrap();
+            write_report(&log, None, &body).unwrap();
+            assert_eq!(
+                write_report(&log, Some("test-agent"), "{\"path\":"),
+                Err(StatusCode::BAD_REQUEST)
+            );
+
+            let written = std::fs::read_to_string(&log).unwrap();
+            let lines = written.lines().collect::<Vec<_>>();
+            assert_eq!(lines.len(), 2);
+            let line =
+                serde_json::from_str::<serde_json::Value>(lines[0]).unwrap();
+            assert_eq!(line["user_agent"], "test-agent");
+            assert_eq!(line["path"], "/naive");
+            assert_eq!(line["events"][0]["kind"], "hydration_end");
+            assert_eq!(line["events"][1]["kind"], "panic");
+            assert!(line["received_ms"].as_u64().unwrap() > 0);
+            assert_eq!(serde_json::from_value::<Report>(line).unwrap(), report);
+        }
+
+        #[test]
+        fn log_rotated_when_full() {
+            let dir = tempfile::tempdir().unwrap();
+            let log = dir.path().join("telemetry.jsonl");
+            let rotated = dir.path().join("telemetry.jsonl.1");
+            for line in ["first", "second", "third"] {
+                append(&log, line, 14).unwrap();
+            }
+            assert_eq!(std::fs::read_to_string(&log).unwrap(), "third\n");
+            assert_eq!(
+                std::fs::read_to_string(&rotated).unwrap(),
+                "first\nsecond\n"
+            );
+        }
+
+        #[test]
+        fn reports_rate_limited_per_client() {
+            let mut clients = HashMap::new();
+            let (a, b) =
+                (Some([10, 0, 0, 1].into()), Some([10, 0, 0, 2].into()));
+            let start = Instant::now();
+            for _ in 0..RATE_LIMIT {
+                assert!(allow(&mut clients, a, start));
+            }
+            assert!(!allow(&mut clients, a, start + RATE_WINDOW / 2));
+            assert!(allow(&mut clients, b, start + RATE_WINDOW / 2));
+            // a new window once the previous one has ended
+            assert!(allow(&mut clients, a, start + RATE_WINDOW));
+            assert_eq!(clients.len(), 2);
+        }
+    }
+}
+
+#[cfg(not(feature = "ssr"))]
+pub use csr::*;
+#[cfg(feature = "ssr")]
+pub use ssr::*;
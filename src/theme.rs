@@ -0,0 +1,169 @@
+// Selection of the highlight.js theme stylesheet, served from the `styles/`
+// of the vendored tree under `/hljs`.  The choice is persisted in a cookie
+// so that the server may render the same stylesheet as the client would.
+use crate::consts::HLJS_THEMES;
+use leptos::prelude::*;
+
+pub const THEME_COOKIE: &str = "hljs_theme";
+// The themes used when following `prefers-color-scheme`.
+const LIGHT_THEME: &str = "github";
+const DARK_THEME: &str = "github-dark";
+
+/// The chosen highlight.js theme, where `None` follows the color scheme
+/// preferred by the browser.
+#[derive(Clone, Copy, Debug)]
+pub struct HighlightThemeContext(pub RwSignal<Option<String>>);
+
+/// Provides the theme signal, initialized from the cookie of the request
+/// under SSR or `document.cookie` under CSR, for `<HighlightTheme>` and
+/// `<ThemeSelect>`.
+pub fn provide_highlight_theme() -> RwSignal<Option<String>> {
+    let theme =
+        RwSignal::new(cookies().as_deref().and_then(theme_from_cookies));
+    provide_context(HighlightThemeContext(theme));
+    #[cfg(feature = "hydrate")]
+    Effect::new(move |_| {
+        use wasm_bindgen::JsCast;
+        let cookie = theme.with(|theme| theme_cookie(theme.as_deref()));
+        let _ = document()
+            .unchecked_into::<web_sys::HtmlDocument>()
+            .set_cookie(&cookie);
+    });
+    theme
+}
+
+// The `document.cookie` assignment persisting the choice, where following
+// the color scheme again removes the cookie.
+#[cfg(any(feature = "hydrate", test))]
+fn theme_cookie(theme: Option<&str>) -> String {
+    match theme {
+        Some(theme) => format!(
+            "{THEME_COOKIE}={theme}; path=/; max-age=31536000; samesite=lax"
+        ),
+        None => format!("{THEME_COOKIE}=; path=/; max-age=0"),
+    }
+}
+
+fn cookies() -> Option<String> {
+    #[cfg(feature = "ssr")]
+    {
+        use_context::<axum::http::request::Parts>().map(|parts| {
+            parts
+                .headers
+                .get_all(axum::http::header::COOKIE)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .collect::<Vec<_>>()
+                .join("; ")
+        })
+    }
+    #[cfg(feature = "hydrate")]
+    {
+        use wasm_bindgen::JsCast;
+        document()
+            .unchecked_into::<web_sys::HtmlDocument>()
+            .cookie()
+            .ok()
+    }
+    #[cfg(not(any(feature = "ssr", feature = "hydrate")))]
+    {
+        None
+    }
+}
+
+// Only accept the names of themes that are actually available, as the
+// value ends up in the `href` of the stylesheet.
+fn theme_from_cookies(cookies: &str) -> Option<String> {
+    cookies
+        .split(';')
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == THEME_COOKIE)
+        .map(|(_, value)| value)
+        .filter(|value| HLJS_THEMES.contains(value))
+        .map(str::to_string)
+}
+
+fn href(theme: &str) -> String {
+    format!("/hljs/styles/{theme}.min.css")
+}
+
+/// The stylesheet(s) for the chosen theme, registered with `leptos_meta` so
+/// they are rendered into the `<head>`, and swapped in place as the theme
+/// changes.  Requires `provide_highlight_theme` to be called by an ancestor.
+#[component]
+pub fn HighlightTheme() -> impl IntoView {
+    use leptos_meta::Link;
+
+    let HighlightThemeContext(theme) = expect_context();
+    move || match theme.get() {
+        Some(theme) => view! {
+            <Link rel="stylesheet" href=href(&theme)/>
+        }
+        .into_any(),
+        None => view! {
+            <Link rel="stylesheet" href=href(LIGHT_THEME)
+                media="(prefers-color-scheme: light)"/>
+            <Link rel="stylesheet" href=href(DARK_THEME)
+                media="(prefers-color-scheme: dark)"/>
+        }
+        .into_any(),
+    }
+}
+
+/// A `<select>` for choosing between the available themes.
+#[component]
+pub fn ThemeSelect() -> impl IntoView {
+    let HighlightThemeContext(theme) = expect_context();
+    let options = HLJS_THEMES
+        .iter()
+        .map(|name| {
+            let selected = move || theme.with(|t| t.as_deref() == Some(name));
+            view! { <option value=*name selected=selected>{*name}</option> }
+        })
+        .collect_view();
+    view! {
+        <label id="theme-select">"Highlight theme "
+            <select on:change=move |ev| {
+                let value = event_target_value(&ev);
+                theme.set(HLJS_THEMES.contains(&value.as_str()).then_some(value));
+            }>
+                <option value="" selected=move || theme.with(Option::is_none)>"Automatic"</option>
+                {options}
+            </select>
+        </label>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn theme_read_from_cookies() {
+        assert_eq!(theme_from_cookies(""), None);
+        assert_eq!(
+            theme_from_cookies("hljs_theme=github").as_deref(),
+            Some("github")
+        );
+        assert_eq!(
+            theme_from_cookies("a=1; hljs_theme=github-dark; b=2").as_deref(),
+            Some("github-dark")
+        );
+        // only the names of the available themes are accepted
+        assert_eq!(theme_from_cookies("hljs_theme=nonexistent"), None);
+        assert_eq!(theme_from_cookies(r#"hljs_theme="><script>"#), None);
+        assert_eq!(theme_from_cookies("other_hljs_theme=github"), None);
+    }
+
+    #[test]
+    fn theme_persisted_to_cookie() {
+        for theme in HLJS_THEMES {
+            let cookie = theme_cookie(Some(theme));
+            let (pair, _) = cookie.split_once(';').unwrap();
+            assert_eq!(theme_from_cookies(pair).as_deref(), Some(*theme));
+        }
+        let cleared = theme_cookie(None);
+        assert!(cleared.contains("max-age=0"));
+        assert_eq!(theme_from_cookies(&cleared), None);
+    }
+}
@@ -0,0 +1,117 @@
+// The tracing of the requests served: every request is given an ID (sent
+// back as `x-request-id`, unless the client provided one) and a span carrying
+// it, within which the middleware, the rendering and the server functions
+// log.  The output is either human readable or JSON lines for a log pipeline,
+// as chosen through `LOG_FORMAT`, and filtered through `RUST_LOG`.
+use axum::{
+    body::Body,
+    http::{HeaderName, Request},
+    Router,
+};
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use thiserror::Error;
+use tower_http::{
+    request_id::{
+        MakeRequestId, PropagateRequestIdLayer, RequestId, SetRequestIdLayer,
+    },
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::Level;
+use tracing_subscriber::EnvFilter;
+
+pub const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Pretty,
+    Json,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("unknown log format {0:?} (expected `pretty` or `json`)")]
+pub struct UnknownLogFormat(String);
+
+impl FromStr for LogFormat {
+    type Err = UnknownLogFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(UnknownLogFormat(s.to_string())),
+        }
+    }
+}
+
+/// Install the subscriber for the `format`, with the levels taken from
+/// `RUST_LOG` (`info` by default).
+pub fn init(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    match format {
+        LogFormat::Pretty => builder.init(),
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .init(),
+    }
+}
+
+// The IDs are only meant to correlate the lines logged for a request, so a
+// counter will do.
+#[derive(Clone, Copy)]
+struct NextRequestId;
+
+impl MakeRequestId for NextRequestId {
+    fn make_request_id<B>(&mut self, _: &Request<B>) -> Option<RequestId> {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        let id = NEXT.fetch_add(1, Ordering::Relaxed);
+        format!("{id:016x}").parse().ok().map(RequestId::new)
+    }
+}
+
+/// Give each of the requests served by the `router` its ID and span.
+pub fn traced(router: Router) -> Router {
+    router
+        .layer(PropagateRequestIdLayer::new(REQUEST_ID))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(|req: &Request<Body>| {
+                    let id = req
+                        .headers()
+                        .get(REQUEST_ID)
+                        .and_then(|id| id.to_str().ok())
+                        .unwrap_or_default();
+                    tracing::info_span!(
+                        "request",
+                        id,
+                        method = %req.method(),
+                        uri = %req.uri(),
+                    )
+                })
+                // a line for every response, with its status and latency
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .layer(SetRequestIdLayer::new(REQUEST_ID, NextRequestId))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_format_from_str() {
+        assert_eq!("pretty".parse(), Ok(LogFormat::Pretty));
+        assert_eq!("json".parse(), Ok(LogFormat::Json));
+        assert_eq!(
+            "xml".parse::<LogFormat>(),
+            Err(UnknownLogFormat("xml".to_string()))
+        );
+    }
+}
@@ -0,0 +1,90 @@
+// Tests of the bindings to the ES module of highlight.js (see `hljs`), run
+// under node through wasm-bindgen-cli's runner:
+//
+//     cargo test --features hydrate --target wasm32-unknown-unknown --test hljs
+//
+// where the runner is installed by `cargo install wasm-bindgen-cli`, at the
+// same version as the `wasm-bindgen` in use (see `.cargo/config.toml`).
+//
+// These are to catch the breakage of the bindings (e.g. a change in the shape
+// of the results) when bumping `@highlightjs/cdn-assets`.
+#![cfg(all(target_arch = "wasm32", not(feature = "ssr")))]
+
+use axum_js_ssr::hljs::{highlight, highlight_ignore_illegals, languages};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+// The text content of the highlighted markup, which should be the code.
+fn text_of(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        rest = &rest[rest.find('>').expect("tags are closed") + 1..];
+    }
+    text.push_str(rest);
+    html_escape::decode_html_entities(&text).into_owned()
+}
+
+#[wasm_bindgen_test]
+fn highlight_rust() {
+    assert_eq!(
+        highlight("fn main() {}".to_string(), "rust".to_string()).as_deref(),
+        Some(
+            "<span class=\"hljs-keyword\">fn</span> \
+             <span class=\"hljs-title function_\">main</span>() {}"
+        ),
+    );
+}
+
+#[wasm_bindgen_test]
+fn highlight_every_language() {
+    let languages = languages();
+    for lang in ["rust", "javascript", "xml", "markdown", "plaintext"] {
+        assert!(languages.iter().any(|l| l == lang), "{lang} is bundled");
+    }
+    let code = "x = \"<a & b>\"; // 1\n";
+    for lang in languages {
+        let html = highlight(code.to_string(), lang.clone())
+            .unwrap_or_else(|| panic!("{lang} failed to highlight"));
+        assert_eq!(text_of(&html), code, "{lang}");
+        assert!(!html.contains("<a "), "{lang} escapes the code");
+    }
+}
+
+#[wasm_bindgen_test]
+fn highlight_unknown_language() {
+    assert_eq!(highlight("x".to_string(), "no-such-lang".to_string()), None);
+    assert_eq!(highlight("x".to_string(), String::new()), None);
+}
+
+#[wasm_bindgen_test]
+fn highlight_illegal_syntax() {
+    let code = "fn main() { </ }";
+    // highlight.js gives up and only escapes the code
+    assert_eq!(
+        highlight(code.to_string(), "rust".to_string()).as_deref(),
+        Some("fn main() { &lt;/ }"),
+    );
+    assert_eq!(
+        highlight_ignore_illegals(code.to_string(), "rust".to_string())
+            .as_deref(),
+        Some(
+            "<span class=\"hljs-keyword\">fn</span> \
+             <span class=\"hljs-title function_\">main</span>() { &lt;/ }"
+        ),
+    );
+}
+
+#[wasm_bindgen_test]
+fn highlight_empty_input() {
+    assert_eq!(
+        highlight(String::new(), "rust".to_string()).as_deref(),
+        Some("")
+    );
+    assert_eq!(
+        highlight_ignore_illegals(String::new(), "plaintext".to_string())
+            .as_deref(),
+        Some("")
+    );
+}
@@ -0,0 +1,191 @@
+// Tests of the server fns, called over HTTP through the same router as the
+// server (and thus with the same context) as the client would.
+#![cfg(feature = "ssr")]
+// the view types of the larger demo pages are deeply nested
+#![recursion_limit = "256"]
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use axum_js_ssr::{
+    api::{
+        Code, FetchCode, FetchCodes, RunCode, StreamCode, FETCH_CODES_MAX_IDS,
+        STREAM_CHUNK_LINES,
+    },
+    server,
+    source::{DirSource, MemorySource, SharedCodeSource},
+};
+use http_body_util::BodyExt;
+use leptos::{prelude::*, server_fn::ServerFn};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+fn app(source: SharedCodeSource) -> Router {
+    let options = LeptosOptions::builder()
+        .output_name("axum_js_ssr")
+        .site_root("target/site")
+        .site_pkg_dir("pkg")
+        .env(Env::DEV)
+        .build();
+    server::router(options, source)
+}
+
+// Call the server fn at `path` with the url-encoded `form`, returning the
+// status and body of the response.
+async fn call(app: Router, path: &str, form: &str) -> (StatusCode, String) {
+    send(app, path, "application/x-www-form-urlencoded", form).await
+}
+
+// As `call`, but for the server fns taking their input as JSON.
+async fn call_json(
+    app: Router,
+    path: &str,
+    json: serde_json::Value,
+) -> (StatusCode, String) {
+    send(app, path, "application/json", &json.to_string()).await
+}
+
+async fn send(
+    app: Router,
+    path: &str,
+    content_type: &str,
+    body: &str,
+) -> (StatusCode, String) {
+    let res = app
+        .oneshot(
+            Request::post(path)
+                .header(header::CONTENT_TYPE, content_type)
+                .header(header::ACCEPT, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = res.status();
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn fetch_code_uses_configured_source() {
+    let source = MemorySource(
+        [("only".to_string(), Code::new("configured", "plaintext"))].into(),
+    );
+    let app = app(Arc::new(source));
+    let (status, body) = call(app.clone(), FetchCode::PATH, "id=only").await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let code: Code = serde_json::from_str(&body).unwrap();
+    assert_eq!(code, Code::new("configured", "plaintext"));
+
+    // rather than falling back to the example programs
+    let (status, body) = call(app, FetchCode::PATH, "id=ch03_05a").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR, "{body}");
+    assert!(body.contains("not found"), "{body}");
+}
+
+#[tokio::test]
+async fn fetch_codes_capped() {
+    let source = MemorySource(
+        [("only".to_string(), Code::new("configured", "plaintext"))].into(),
+    );
+    let app = app(Arc::new(source));
+    let ids = vec!["only"; FETCH_CODES_MAX_IDS];
+    let (status, body) = call_json(
+        app.clone(),
+        FetchCodes::PATH,
+        serde_json::json!({ "ids": ids, "lang": null }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let codes: Vec<Result<Code, serde_json::Value>> =
+        serde_json::from_str(&body).unwrap();
+    assert_eq!(codes.len(), FETCH_CODES_MAX_IDS);
+
+    let ids = vec!["only"; FETCH_CODES_MAX_IDS + 1];
+    let (status, body) = call_json(
+        app,
+        FetchCodes::PATH,
+        serde_json::json!({ "ids": ids, "lang": null }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR, "{body}");
+    assert!(body.contains("exceeding the limit"), "{body}");
+}
+
+#[tokio::test]
+async fn server_fns_not_timed_as_ssr_render() {
+    let source = MemorySource(
+        [("only".to_string(), Code::new("configured", "plaintext"))].into(),
+    );
+    let res = app(Arc::new(source))
+        .oneshot(
+            Request::post(FetchCode::PATH)
+                .header(
+                    header::CONTENT_TYPE,
+                    "application/x-www-form-urlencoded",
+                )
+                .header(header::ACCEPT, "application/json")
+                .body(Body::from("id=only"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let timing = res.headers()["server-timing"].to_str().unwrap();
+    assert!(timing.contains("fetch_code;"), "{timing}");
+    assert!(!timing.contains("ssr-render"), "{timing}");
+}
+
+#[tokio::test]
+async fn run_code_disabled_by_default() {
+    let app = app(Arc::new(MemorySource::default()));
+    let (status, body) =
+        call(app, RunCode::PATH, "code=fn+main()+%7B%7D").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR, "{body}");
+    assert!(body.contains("running code is disabled"), "{body}");
+}
+
+#[tokio::test]
+async fn stream_code_streams_from_source() {
+    let dir = tempfile::tempdir().unwrap();
+    let lines = (1..=STREAM_CHUNK_LINES * 2 + 50)
+        .map(|n| format!("// line {n}\n"))
+        .collect::<Vec<_>>();
+    let mut code = lines.concat();
+    code.push_str("// ANCHOR: tail\n// the tail\n// ANCHOR_END: tail\n");
+    std::fs::write(dir.path().join("large.rs"), &code).unwrap();
+    let app = app(Arc::new(DirSource::new(dir.path()).unwrap()));
+
+    // everything as it is, without a selection
+    let (status, body) =
+        call(app.clone(), StreamCode::PATH, "id=large.rs&skip=0").await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body, code);
+
+    // carrying on after the lines the client already has
+    let (status, body) =
+        call(app.clone(), StreamCode::PATH, "id=large.rs&skip=200").await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body.starts_with("// line 201\n"), "{body}");
+    assert!(body.ends_with("// ANCHOR_END: tail\n"), "{body}");
+
+    // the selections apply as they do for fetch_code
+    let (status, body) =
+        call(app.clone(), StreamCode::PATH, "id=large.rs%23L3-L5&skip=1").await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body, "// line 4\n// line 5\n");
+    let (status, body) =
+        call(app.clone(), StreamCode::PATH, "id=large.rs%23tail&skip=0").await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body, "// the tail\n");
+
+    let (status, body) =
+        call(app.clone(), StreamCode::PATH, "id=missing.rs&skip=0").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR, "{body}");
+    assert!(body.contains("not found"), "{body}");
+    let (status, body) =
+        call(app, StreamCode::PATH, "id=large.rs&lang=javascript&skip=0").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR, "{body}");
+}
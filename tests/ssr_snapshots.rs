@@ -0,0 +1,348 @@
+// Snapshot tests of the HTML rendered through SSR for every route of `App`,
+// served in-process through the same router as the server.  Anything that
+// varies between renders (the CSP nonce) or is an implementation detail of
+// the hydration (the markers and the IDs of the suspended fragments) is
+// normalized before the comparison against the snapshots committed under
+// `tests/snapshots`.
+//
+// To accept intentional changes to the output, run with `UPDATE_SNAPSHOTS=1`
+// to have the snapshots (re)written instead of compared.
+#![cfg(feature = "ssr")]
+// the view types of the larger demo pages are deeply nested
+#![recursion_limit = "256"]
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use axum_js_ssr::{
+    app::App,
+    compare::Frame,
+    consts::{CH03_05A, CH05_02A, HIGHLIGHT_ES_MIN_JS_SRI},
+    server,
+    source::MemorySource,
+};
+use http_body_util::BodyExt;
+use leptos::prelude::*;
+use leptos_axum::generate_route_list;
+use regex::Regex;
+use std::{path::PathBuf, sync::Arc};
+use tower::ServiceExt;
+
+fn app() -> Router {
+    app_at("target/site")
+}
+
+fn app_at(site_root: &str) -> Router {
+    let options = LeptosOptions::builder()
+        .output_name("axum_js_ssr")
+        .site_root(site_root)
+        .site_pkg_dir("pkg")
+        .env(Env::DEV)
+        .build();
+    server::router(options, Arc::new(MemorySource::consts()))
+}
+
+async fn render(path: &str) -> String {
+    render_with(app(), path).await
+}
+
+async fn render_with(app: Router, path: &str) -> String {
+    render_request(app, Request::get(path).body(Body::empty()).unwrap()).await
+}
+
+async fn render_request(app: Router, req: Request<Body>) -> String {
+    let path = req.uri().to_string();
+    let res = app.oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK, "{path}");
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).expect("HTML is UTF-8")
+}
+
+/// Strip what varies between renders and between Leptos releases, and break
+/// the lines between tags for the snapshots to be reviewable as diffs.
+fn normalize(html: &str) -> String {
+    let mut html = html.to_string();
+    let nonce = Regex::new(r#"nonce="([^"]+)""#).unwrap();
+    if let Some(value) = nonce.captures(&html).map(|c| c[1].to_string()) {
+        html = html.replace(&value, "NONCE");
+    }
+    let suspense = Regex::new(r"\b(s-)?\d+(-\d+)*-([ocf])\b").unwrap();
+    let fragment = Regex::new(r#"let id = "\d+(-\d+)*-""#).unwrap();
+    html = html.replace("<!>", "");
+    html = suspense.replace_all(&html, "${1}ID-$3").into_owned();
+    html = fragment
+        .replace_all(&html, r#"let id = "ID-""#)
+        .into_owned();
+    html.replace("><", ">\n<")
+}
+
+fn snapshot_path(route: &str) -> PathBuf {
+    let name = match route.trim_start_matches('/') {
+        "" => "index",
+        name => name,
+    };
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.html"))
+}
+
+// The structure every page is expected to have, regardless of the snapshot.
+fn check_invariants(route: &str, html: &str) {
+    assert!(html.starts_with("<!DOCTYPE html>"), "{route}: doctype");
+    for id in ["notice", "reset", "theme-select"] {
+        assert!(html.contains(&format!(r#"id="{id}""#)), "{route}: #{id}");
+    }
+    assert!(
+        html.contains(r#"rel="modulepreload""#),
+        "{route}: hydration scripts"
+    );
+}
+
+#[tokio::test]
+async fn code_inner_renders_escaped_text() {
+    // without the markup of highlight.js, which is only applied on the client
+    for route in ["/wasm-bindgen-direct", "/wasm-bindgen-direct-fixed"] {
+        let html = render(route).await;
+        assert!(
+            html.contains(
+                "&lt;pre&gt;&lt;code inner_html=inner&gt;&lt;/code&gt;\
+                 &lt;/pre&gt;"
+            ),
+            "{route}"
+        );
+        assert!(!html.contains("<code inner_html"), "{route}");
+        assert!(!html.contains(r#"class="hljs-"#), "{route}");
+    }
+}
+
+#[tokio::test]
+async fn copy_buttons_link_to_raw_code() {
+    // the buttons of the code loaded from the source work without a client
+    let html = render("/naive").await;
+    for href in ["/code/ch03_05a", "/code/ch05_02a"] {
+        assert!(
+            html.contains(&format!(
+                r#"<a role="button" href="{href}" download class="copy-button">"#
+            )),
+            "{href}"
+        );
+    }
+    let html = render("/").await;
+    assert!(html.contains(r#"href="/code/ch05_02a%23L11-L13" download"#));
+
+    assert_eq!(render("/code/ch03_05a").await, CH03_05A);
+    let excerpt = CH05_02A.lines().skip(10).take(3).collect::<Vec<_>>();
+    assert_eq!(
+        render("/code/ch05_02a%23L11-L13").await,
+        excerpt.join("\n") + "\n"
+    );
+    let res = app()
+        .oneshot(Request::get("/code/missing").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn code_lines_render_one_line_per_row() {
+    let html = render("/").await;
+    let start = html.find(r#"<pre class="code-lines"><code>"#).unwrap();
+    let end = start + html[start..].find("</code></pre>").unwrap();
+    let code_lines = &html[start..end];
+    // the rows come from nothing but the `.line` elements, as there are no
+    // newlines between them to add any rows of their own
+    assert!(!code_lines.contains('\n'), "{code_lines}");
+    let line = Regex::new(
+        r#"<span class="line( highlighted)?"><span class="line-number">(\d+)</span>"#,
+    )
+    .unwrap();
+    let lines = line
+        .captures_iter(code_lines)
+        .map(|c| (c[2].to_string(), c.get(1).is_some()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        [
+            ("11".to_string(), false),
+            ("12".to_string(), true),
+            ("13".to_string(), false),
+        ]
+    );
+    // which are displayed as blocks, i.e. one per row
+    let style = std::fs::read_to_string(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("style/main.scss"),
+    )
+    .unwrap();
+    let rule = style
+        .split_once(".code-lines .line {")
+        .and_then(|(_, rule)| rule.split_once('}'))
+        .map(|(rule, _)| rule)
+        .expect("style for the lines");
+    assert!(rule.contains("display: block;"), "{rule}");
+}
+
+#[tokio::test]
+async fn editor_renders_plain_source() {
+    let html = render("/editor").await;
+    let start = html.find(r#"<div class="code-editor">"#).unwrap();
+    let end = start + html[start..].find("</textarea>").unwrap();
+    let editor = &html[start..end];
+    // the source is left to be highlighted by highlight.js once hydrated,
+    // which is also when the textarea becomes editable
+    let source = html_escape::encode_text(CH05_02A);
+    assert!(
+        editor.contains(&format!(r#"<code class="hljs">{source} </code>"#)),
+        "{editor}"
+    );
+    assert!(!editor.contains("hljs-"), "{editor}");
+    assert!(editor.contains("readonly>fn main() {"), "{editor}");
+}
+
+// The part of the page within `<head>`.
+fn head(html: &str) -> &str {
+    let end = html.find("</head>").expect("page has a head");
+    &html[..end]
+}
+
+#[tokio::test]
+async fn theme_rendered_into_head() {
+    let html = render("/").await;
+    let (head_html, body) = html.split_at(html.find("</head>").unwrap());
+    for (theme, scheme) in [("github", "light"), ("github-dark", "dark")] {
+        assert!(
+            head_html.contains(&format!(
+                r#"<link href="/hljs/styles/{theme}.min.css" media="(prefers-color-scheme: {scheme})" rel="stylesheet">"#
+            )),
+            "{head_html}"
+        );
+    }
+    assert!(!body.contains("/hljs/styles/"));
+
+    for (cookie, expected) in [
+        ("hljs_theme=github", Some("github")),
+        ("a=1; hljs_theme=github-dark", Some("github-dark")),
+        ("hljs_theme=nonexistent", None),
+    ] {
+        let req = Request::get("/")
+            .header("cookie", cookie)
+            .body(Body::empty())
+            .unwrap();
+        let html = render_request(app(), req).await;
+        let head = head(&html);
+        match expected {
+            Some(theme) => {
+                assert!(
+                    head.contains(&format!(
+                        r#"<link href="/hljs/styles/{theme}.min.css" rel="stylesheet">"#
+                    )),
+                    "{cookie}: {head}"
+                );
+                assert!(!head.contains("prefers-color-scheme"), "{cookie}");
+            }
+            None => assert_eq!(
+                head.matches("prefers-color-scheme").count(),
+                2,
+                "{cookie}"
+            ),
+        }
+    }
+}
+
+#[tokio::test]
+async fn hljs_module_preloaded_with_integrity() {
+    let site = tempfile::tempdir().unwrap();
+    let module = "pkg/snippets/axum_js_ssr-0123abcd/\
+                  node_modules/@highlightjs/cdn-assets/es/highlight.min.js";
+    let path = site.path().join(module);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "export default {};").unwrap();
+
+    let html =
+        render_with(app_at(site.path().to_str().unwrap()), "/naive").await;
+    let preload = format!(
+        r#"<link rel="modulepreload" href="/{module}" integrity="{}" crossorigin="anonymous">"#,
+        HIGHLIGHT_ES_MIN_JS_SRI
+    );
+    assert_eq!(html.contains(&preload), !cfg!(feature = "no-sri"), "{html}");
+}
+
+#[tokio::test]
+async fn nav_stays_under_ssr_mode() {
+    let html = render("/ssr/in-order/naive").await;
+    assert!(
+        html.contains(r#"href="/ssr/in-order/naive" aria-current="page""#),
+        "{html}"
+    );
+    assert!(
+        html.contains(r#"href="/ssr/in-order/streaming" data-tags"#),
+        "{html}"
+    );
+    assert!(html.contains("Rendered with SSR mode <strong>in-order"));
+
+    let html = render("/naive").await;
+    assert!(
+        html.contains(r#"href="/naive" aria-current="page""#),
+        "{html}"
+    );
+    assert!(html.contains(r#"href="/streaming" data-tags"#), "{html}");
+}
+
+#[tokio::test]
+async fn compare_frames_ignore_ssr_mode_cookie() {
+    // rendered with the mode of the example rather than redirected
+    for frame in [Frame::Ssr, Frame::Html] {
+        let req = Request::get(frame.src("naive"))
+            .header(header::COOKIE, "ssr_mode=in-order")
+            .body(Body::empty())
+            .unwrap();
+        let html = render_request(app(), req).await;
+        assert!(
+            html.contains("Rendered with SSR mode <strong>async"),
+            "{frame:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn every_route_matches_snapshot() {
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let mut mismatched = Vec::new();
+    for listing in generate_route_list(App) {
+        let route = listing.path();
+        let html = render(route).await;
+        check_invariants(route, &html);
+        let html = normalize(&html);
+
+        let path = snapshot_path(route);
+        if update {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, &html).unwrap();
+            continue;
+        }
+        let Ok(expected) = std::fs::read_to_string(&path) else {
+            mismatched.push(format!("{route}: no snapshot"));
+            continue;
+        };
+        if let Some((n, (expected, actual))) = expected
+            .lines()
+            .chain(std::iter::repeat(""))
+            .zip(html.lines().chain(std::iter::repeat("")))
+            .take(expected.lines().count().max(html.lines().count()))
+            .enumerate()
+            .find(|(_, (expected, actual))| expected != actual)
+        {
+            mismatched.push(format!(
+                "{route}: line {}\n  expected: {expected}\n  actual:   {actual}",
+                n + 1
+            ));
+        }
+    }
+    assert!(
+        mismatched.is_empty(),
+        "SSR output differs from the snapshots (rerun with \
+         UPDATE_SNAPSHOTS=1 to accept the changes):\n{}",
+        mismatched.join("\n")
+    );
+}